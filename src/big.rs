@@ -0,0 +1,371 @@
+//! Arbitrary-precision integers, for LEB128 values that don't fit in 128 bits.
+//!
+//! Only what encoding, decoding, parsing and printing LEB128 values needs is implemented. The
+//! public API is construction, `Display` and `FromStr`; the arithmetic the codec needs is
+//! internal to the crate.
+
+use std::fmt;
+use std::str::FromStr;
+
+/// An arbitrary-precision unsigned integer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigUint {
+    /// Little-endian 32-bit limbs. Never ends with a zero limb, so every number has a single
+    /// representation and zero has no limbs.
+    limbs: Vec<u32>,
+}
+
+/// An arbitrary-precision signed integer.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BigInt {
+    /// Never set for zero.
+    negative: bool,
+    magnitude: BigUint,
+}
+
+/// An error parsing a `BigUint` or `BigInt`. Messages are the same as `ParseIntError`'s.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// There are no digits, e.g. `""`, `"-"` or `"0x"`.
+    Empty,
+
+    /// A character isn't a digit of the radix.
+    InvalidDigit,
+}
+
+impl BigUint {
+    /// The number with the given digits in base `radix`, most significant first. Returns `None`
+    /// if `radix` isn't in `2..=36` or a digit isn't less than `radix`.
+    pub fn from_radix_digits(radix: u32, digits: &[u32]) -> Option<BigUint> {
+        if !(2..=36).contains(&radix) || digits.iter().any(|digit| *digit >= radix) {
+            return None;
+        }
+        let mut n = BigUint::default();
+        for digit in digits {
+            n.mul_add_small(radix, *digit);
+        }
+        Some(n)
+    }
+
+    pub(crate) fn is_zero(&self) -> bool {
+        self.limbs.is_empty()
+    }
+
+    /// Number of significant bits, 0 for zero.
+    pub(crate) fn bits(&self) -> u64 {
+        match self.limbs.last() {
+            Some(last) => self.limbs.len() as u64 * 32 - last.leading_zeros() as u64,
+            None => 0,
+        }
+    }
+
+    fn limb(&self, i: usize) -> u32 {
+        self.limbs.get(i).copied().unwrap_or(0)
+    }
+
+    fn normalize(&mut self) {
+        while self.limbs.last() == Some(&0) {
+            self.limbs.pop();
+        }
+    }
+
+    /// The 7 bits starting at bit `shift`.
+    pub(crate) fn seven_bits_at(&self, shift: u64) -> u8 {
+        let i = (shift / 32) as usize;
+        let pair = self.limb(i) as u64 | (self.limb(i + 1) as u64) << 32;
+        ((pair >> (shift % 32)) & 0x7f) as u8
+    }
+
+    /// Sets the 7 bits starting at bit `shift` to `bits`. The bits must be clear.
+    pub(crate) fn set_seven_bits_at(&mut self, shift: u64, bits: u8) {
+        debug_assert_eq!(
+            self.seven_bits_at(shift),
+            0,
+            "Setting bits that aren't clear"
+        );
+        if bits == 0 {
+            return;
+        }
+        let i = (shift / 32) as usize;
+        let pair = ((bits & 0x7f) as u64) << (shift % 32);
+        let needed = if pair >> 32 != 0 { i + 2 } else { i + 1 };
+        if self.limbs.len() < needed {
+            self.limbs.resize(needed, 0);
+        }
+        self.limbs[i] |= pair as u32;
+        if pair >> 32 != 0 {
+            self.limbs[i + 1] |= (pair >> 32) as u32;
+        }
+    }
+
+    /// `self * mul + add`.
+    pub(crate) fn mul_add_small(&mut self, mul: u32, add: u32) {
+        let mut carry = add as u64;
+        for limb in &mut self.limbs {
+            let n = *limb as u64 * mul as u64 + carry;
+            *limb = n as u32;
+            carry = n >> 32;
+        }
+        if carry != 0 {
+            self.limbs.push(carry as u32);
+        }
+        self.normalize();
+    }
+
+    /// Divides `self` by `div` in place, returning the remainder.
+    fn div_rem_small(&mut self, div: u32) -> u32 {
+        let mut rem = 0u64;
+        for limb in self.limbs.iter_mut().rev() {
+            let n = rem << 32 | *limb as u64;
+            *limb = (n / div as u64) as u32;
+            rem = n % div as u64;
+        }
+        self.normalize();
+        rem as u32
+    }
+
+    /// `self + 1`.
+    pub(crate) fn add_one(&mut self) {
+        self.mul_add_small(1, 1);
+    }
+
+    /// `self - 1`. Panics if `self` is zero.
+    pub(crate) fn sub_one(&mut self) {
+        assert!(!self.is_zero(), "Subtracting one from zero");
+        for limb in &mut self.limbs {
+            if *limb == 0 {
+                *limb = u32::MAX;
+            } else {
+                *limb -= 1;
+                break;
+            }
+        }
+        self.normalize();
+    }
+
+    /// `2^width - 1 - self`, i.e. the low `width` bits of `self` flipped. `self` must be less than
+    /// `2^width`.
+    pub(crate) fn complement(&self, width: u64) -> BigUint {
+        debug_assert!(
+            self.bits() <= width,
+            "Complementing a value wider than {}",
+            width
+        );
+        let n_limbs = width.div_ceil(32) as usize;
+        let mut limbs: Vec<u32> = (0..n_limbs).map(|i| !self.limb(i)).collect();
+        if !width.is_multiple_of(32) {
+            limbs[n_limbs - 1] &= (1 << (width % 32)) - 1;
+        }
+        let mut result = BigUint { limbs };
+        result.normalize();
+        result
+    }
+}
+
+impl From<u128> for BigUint {
+    fn from(mut n: u128) -> Self {
+        let mut limbs = vec![];
+        while n != 0 {
+            limbs.push(n as u32);
+            n >>= 32;
+        }
+        BigUint { limbs }
+    }
+}
+
+impl fmt::Display for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        // Nine decimal digits at a time, least significant first.
+        let mut n = self.clone();
+        let mut chunks = vec![];
+        loop {
+            chunks.push(n.div_rem_small(1_000_000_000));
+            if n.is_zero() {
+                break;
+            }
+        }
+
+        let mut s = chunks.pop().unwrap().to_string();
+        for chunk in chunks.iter().rev() {
+            s.push_str(&format!("{:09}", chunk));
+        }
+        f.pad_integral(true, "", &s)
+    }
+}
+
+impl fmt::LowerHex for BigUint {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let mut s = format!("{:x}", self.limb(self.limbs.len().saturating_sub(1)));
+        for limb in self.limbs.iter().rev().skip(1) {
+            s.push_str(&format!("{:08x}", limb));
+        }
+        f.pad_integral(true, "0x", &s)
+    }
+}
+
+impl FromStr for BigUint {
+    type Err = ParseError;
+
+    /// Parses a decimal or `0x`-prefixed hexadecimal number.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (radix, digits) = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+            Some(hex) => (16, hex),
+            None => (10, s),
+        };
+        if digits.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        let mut n = BigUint::default();
+        for c in digits.chars() {
+            let digit = c.to_digit(radix).ok_or(ParseError::InvalidDigit)?;
+            n.mul_add_small(radix, digit);
+        }
+        Ok(n)
+    }
+}
+
+impl BigInt {
+    /// The number with the given sign and magnitude. Zero is never negative, `negative` is
+    /// ignored when `magnitude` is zero.
+    pub fn new(negative: bool, magnitude: BigUint) -> BigInt {
+        BigInt {
+            negative: negative && !magnitude.is_zero(),
+            magnitude,
+        }
+    }
+
+    /// Whether the number is less than zero.
+    pub fn is_negative(&self) -> bool {
+        self.negative
+    }
+
+    /// The absolute value of the number.
+    pub fn magnitude(&self) -> &BigUint {
+        &self.magnitude
+    }
+}
+
+impl From<i128> for BigInt {
+    fn from(n: i128) -> Self {
+        BigInt::new(n < 0, BigUint::from(n.unsigned_abs()))
+    }
+}
+
+impl From<BigUint> for BigInt {
+    fn from(n: BigUint) -> Self {
+        BigInt::new(false, n)
+    }
+}
+
+impl fmt::Display for BigInt {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        f.pad_integral(!self.negative, "", &self.magnitude.to_string())
+    }
+}
+
+impl FromStr for BigInt {
+    type Err = ParseError;
+
+    /// Parses a decimal or `0x`-prefixed hexadecimal number, with an optional `-`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.strip_prefix('-') {
+            Some(digits) => Ok(BigInt::new(true, digits.parse()?)),
+            None => Ok(BigInt::new(false, s.parse()?)),
+        }
+    }
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            ParseError::Empty => write!(f, "cannot parse integer from empty string"),
+            ParseError::InvalidDigit => write!(f, "invalid digit found in string"),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_display_and_parse() {
+        for n in [
+            0,
+            1,
+            999_999_999,
+            1_000_000_000,
+            u64::MAX as u128,
+            u128::MAX,
+        ] {
+            let big = BigUint::from(n);
+            assert_eq!(big.to_string(), n.to_string());
+            assert_eq!(format!("{:x}", big), format!("{:x}", n));
+            assert_eq!(n.to_string().parse::<BigUint>(), Ok(big.clone()));
+            assert_eq!(format!("0x{:x}", n).parse::<BigUint>(), Ok(big.clone()));
+        }
+
+        let s = "1".to_owned() + &"0".repeat(50);
+        let big: BigUint = s.parse().unwrap();
+        assert_eq!(big.to_string(), s);
+        assert_eq!(big.bits(), 167);
+
+        for n in [i128::MIN, -1, 0, 1, i128::MAX] {
+            let big = BigInt::from(n);
+            assert_eq!(big.to_string(), n.to_string());
+            assert_eq!(n.to_string().parse::<BigInt>(), Ok(big.clone()));
+        }
+        assert_eq!("-0".parse::<BigInt>().unwrap().to_string(), "0");
+
+        assert_eq!("".parse::<BigUint>(), Err(ParseError::Empty));
+        assert_eq!("0x".parse::<BigUint>(), Err(ParseError::Empty));
+        assert_eq!("12a".parse::<BigUint>(), Err(ParseError::InvalidDigit));
+        assert_eq!("-".parse::<BigInt>(), Err(ParseError::Empty));
+
+        assert_eq!(
+            BigUint::from_radix_digits(2, &[1, 0, 1, 1]),
+            Some(BigUint::from(0b1011))
+        );
+        assert_eq!(
+            BigUint::from_radix_digits(16, &[]),
+            Some(BigUint::default())
+        );
+        assert_eq!(BigUint::from_radix_digits(8, &[8]), None);
+        assert_eq!(BigUint::from_radix_digits(1, &[0]), None);
+    }
+
+    #[test]
+    fn test_bits() {
+        let mut n = BigUint::default();
+        for (i, bits) in [0x65, 0x0e, 0x26, 0x7f, 0x01, 0x00].iter().enumerate() {
+            n.set_seven_bits_at(7 * i as u64, *bits);
+        }
+        assert_eq!(
+            n,
+            BigUint::from(0x65 | 0x0e << 7 | 0x26 << 14 | 0x7f << 21 | 1 << 28)
+        );
+        for (i, bits) in [0x65, 0x0e, 0x26, 0x7f, 0x01, 0x00].iter().enumerate() {
+            assert_eq!(n.seven_bits_at(7 * i as u64), *bits);
+        }
+        assert_eq!(n.bits(), 29);
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let mut n = BigUint::from(u32::MAX as u128);
+        n.add_one();
+        assert_eq!(n, BigUint::from(1 << 32));
+        n.sub_one();
+        assert_eq!(n, BigUint::from(u32::MAX as u128));
+
+        assert_eq!(BigUint::from(0).complement(7), BigUint::from(0x7f));
+        assert_eq!(
+            BigUint::from(1).complement(64),
+            BigUint::from(u64::MAX as u128 - 1)
+        );
+        assert_eq!(BigUint::from(u128::MAX).complement(128), BigUint::default());
+    }
+}
@@ -0,0 +1,1034 @@
+//! Command line parsing and usage text.
+//!
+//! The same parser is used for the process arguments and for lines typed into the REPL.
+
+use super::output::{self, Format};
+
+use std::fmt;
+use std::path::PathBuf;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cmd {
+    EncodeSigned,
+    EncodeUnsigned,
+    DecodeSigned,
+    DecodeUnsigned,
+    /// Decode as both unsigned and signed.
+    DecodeBoth,
+}
+
+const CMDS: &[Cmd] = &[
+    Cmd::EncodeSigned,
+    Cmd::EncodeUnsigned,
+    Cmd::DecodeSigned,
+    Cmd::DecodeUnsigned,
+    Cmd::DecodeBoth,
+];
+
+impl Cmd {
+    /// Short name of the command, e.g. `es`.
+    pub fn short_name(self) -> &'static str {
+        match self {
+            Cmd::EncodeSigned => "es",
+            Cmd::EncodeUnsigned => "eu",
+            Cmd::DecodeSigned => "ds",
+            Cmd::DecodeUnsigned => "du",
+            Cmd::DecodeBoth => "db",
+        }
+    }
+
+    /// Long name of the command, e.g. `encode-signed`.
+    pub fn long_name(self) -> &'static str {
+        match self {
+            Cmd::EncodeSigned => "encode-signed",
+            Cmd::EncodeUnsigned => "encode-unsigned",
+            Cmd::DecodeSigned => "decode-signed",
+            Cmd::DecodeUnsigned => "decode-unsigned",
+            Cmd::DecodeBoth => "decode-both",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Cmd> {
+        CMDS.iter()
+            .copied()
+            .find(|cmd| cmd.short_name() == name || cmd.long_name() == name)
+    }
+
+    pub fn is_encode(self) -> bool {
+        matches!(self, Cmd::EncodeSigned | Cmd::EncodeUnsigned)
+    }
+
+    fn summary(self) -> &'static str {
+        match self {
+            Cmd::EncodeSigned => "Encode a signed number",
+            Cmd::EncodeUnsigned => "Encode an unsigned number",
+            Cmd::DecodeSigned => "Decode a signed number from hex bytes",
+            Cmd::DecodeUnsigned => "Decode an unsigned number from hex bytes",
+            Cmd::DecodeBoth => "Decode hex bytes as both unsigned and signed, side by side",
+        }
+    }
+
+    fn arg_name(self) -> &'static str {
+        if self.is_encode() {
+            "<EXPR>..."
+        } else {
+            "<BYTES>..."
+        }
+    }
+
+    fn arg_help(self) -> &'static str {
+        match self {
+            Cmd::EncodeSigned => {
+                "Numbers or expressions, e.g. `-123_456`, `0xff`, `-(2**31)` or `i64::MIN`, \
+                 encoded in turn. Quote expressions with spaces"
+            }
+            Cmd::EncodeUnsigned => {
+                "Numbers or expressions, e.g. `123_456`, `0b1010`, `1 << 35` or `u32::MAX`, \
+                 encoded in turn. Quote expressions with spaces"
+            }
+            Cmd::DecodeSigned | Cmd::DecodeUnsigned | Cmd::DecodeBoth => {
+                "Hex bytes, e.g. `e5 8e 26`, `e58e26`, `0xe5,0x8e,0x26` or `\\xe5\\x8e\\x26`. \
+                 Multiple arguments are joined with spaces"
+            }
+        }
+    }
+}
+
+/// An option accepted by one or more commands.
+struct OptSpec {
+    short: Option<char>,
+    long: &'static str,
+    /// Name of the option's value in the help text, `None` for flags.
+    value: Option<&'static str>,
+    help: &'static str,
+    /// Commands accepting the option. Empty means all commands.
+    cmds: &'static [Cmd],
+}
+
+impl OptSpec {
+    fn applies_to(&self, cmd: Cmd) -> bool {
+        self.cmds.is_empty() || self.cmds.contains(&cmd)
+    }
+
+    fn matches(&self, name: &str) -> bool {
+        match name.strip_prefix("--") {
+            Some(long) => long == self.long,
+            None => {
+                let mut chars = name[1..].chars();
+                chars.next() == self.short && chars.next().is_none()
+            }
+        }
+    }
+}
+
+const OPTIONS: &[OptSpec] = &[
+    OptSpec {
+        short: Some('h'),
+        long: "help",
+        value: None,
+        help: "Print help",
+        cmds: &[],
+    },
+    OptSpec {
+        short: Some('a'),
+        long: "all",
+        value: None,
+        help: "Decode all values in the input, printing the offset and length of each",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned],
+    },
+    OptSpec {
+        short: Some('d'),
+        long: "dump",
+        value: None,
+        help: "Parse the input as the output of xxd, hexdump -C, od -tx1, gdb or lldb. \
+               Inputs read from a file or stdin are parsed as a single dump",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned, Cmd::DecodeBoth],
+    },
+    OptSpec {
+        short: Some('b'),
+        long: "binary",
+        value: Some("<PATH>"),
+        help: "Decode raw bytes of a binary file instead of hex bytes",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned, Cmd::DecodeBoth],
+    },
+    OptSpec {
+        short: None,
+        long: "offset",
+        value: Some("<N>"),
+        help: "With --binary, start decoding at byte offset N",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned, Cmd::DecodeBoth],
+    },
+    OptSpec {
+        short: None,
+        long: "length",
+        value: Some("<N>"),
+        help: "With --binary, read at most N bytes",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned, Cmd::DecodeBoth],
+    },
+    OptSpec {
+        short: Some('n'),
+        long: "count",
+        value: Some("<N>"),
+        help: "Decode at most N values, printing the offset and length of each",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned],
+    },
+    OptSpec {
+        short: Some('s'),
+        long: "strict",
+        value: None,
+        help: "Reject encodings longer than necessary, e.g. `80 00` for 0",
+        cmds: &[Cmd::DecodeSigned, Cmd::DecodeUnsigned],
+    },
+    OptSpec {
+        short: None,
+        long: "bits",
+        value: Some("<N>"),
+        help: "Values are N bits wide, e.g. 32 for u32 and i32. Default: 128",
+        cmds: &[],
+    },
+    OptSpec {
+        short: None,
+        long: "big",
+        value: None,
+        help: "Values can be any size rather than at most 128 bits. The encode commands then \
+               take plain numbers, not expressions",
+        cmds: &[
+            Cmd::EncodeSigned,
+            Cmd::EncodeUnsigned,
+            Cmd::DecodeSigned,
+            Cmd::DecodeUnsigned,
+        ],
+    },
+    OptSpec {
+        short: Some('p'),
+        long: "pad",
+        value: Some("<N|max>"),
+        help: "Pad the encoding to N bytes, or to the most a value of --bits can take",
+        cmds: &[Cmd::EncodeSigned, Cmd::EncodeUnsigned],
+    },
+    OptSpec {
+        short: Some('c'),
+        long: "concat",
+        value: None,
+        help: "Print the encodings of all values as one byte stream, e.g. the body of a \
+               WebAssembly vec, rather than one per line",
+        cmds: &[Cmd::EncodeSigned, Cmd::EncodeUnsigned],
+    },
+    OptSpec {
+        short: Some('f'),
+        long: "format",
+        value: Some("<FORMAT>"),
+        help: "Format of the encoded bytes, see below. Default: hex",
+        cmds: &[Cmd::EncodeSigned, Cmd::EncodeUnsigned],
+    },
+    OptSpec {
+        short: Some('U'),
+        long: "upper",
+        value: None,
+        help: "Print hex digits in upper case",
+        cmds: &[Cmd::EncodeSigned, Cmd::EncodeUnsigned],
+    },
+    OptSpec {
+        short: Some('x'),
+        long: "explain",
+        value: None,
+        help: "Show how the value is encoded or decoded, one byte at a time",
+        cmds: &[
+            Cmd::EncodeSigned,
+            Cmd::EncodeUnsigned,
+            Cmd::DecodeSigned,
+            Cmd::DecodeUnsigned,
+        ],
+    },
+    OptSpec {
+        short: Some('j'),
+        long: "json",
+        value: None,
+        help: "Print results as JSON objects, one per line",
+        cmds: &[],
+    },
+    OptSpec {
+        short: Some('i'),
+        long: "input",
+        value: Some("<PATH>"),
+        help: "Read inputs from a file, one per line. `-` reads standard input",
+        cmds: &[],
+    },
+    OptSpec {
+        short: Some('k'),
+        long: "keep-going",
+        value: None,
+        help: "With multiple inputs, report bad inputs and continue",
+        cmds: &[],
+    },
+    OptSpec {
+        short: Some('V'),
+        long: "version",
+        value: None,
+        help: "Print version",
+        cmds: &[],
+    },
+];
+
+/// What the user asked for.
+#[derive(Debug)]
+pub enum Invocation {
+    /// No command given. On the command line this starts the REPL.
+    Empty,
+
+    /// Print help, for a command if one is given.
+    Help(Option<Cmd>),
+
+    /// Print the version.
+    Version,
+
+    /// Run a command.
+    Run(Args),
+}
+
+/// Where a command gets its input from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Source {
+    /// From the command line arguments. Encode commands encode each argument in turn, decode
+    /// commands join them with spaces.
+    Args(Vec<String>),
+
+    /// From standard input, one input per line.
+    Stdin,
+
+    /// From a file, one input per line.
+    File(PathBuf),
+
+    /// Raw bytes of a binary file.
+    Binary(PathBuf),
+}
+
+/// A command with its arguments and options.
+#[derive(Debug)]
+pub struct Args {
+    pub cmd: Cmd,
+    pub source: Source,
+
+    /// Whether to continue with the next input after an error when there are multiple inputs.
+    pub keep_going: bool,
+
+    /// Whether to decode all values in the input rather than just the first one.
+    pub all: bool,
+
+    /// Whether the input is the output of a hex dump tool.
+    pub dump: bool,
+
+    /// Format of the encoded bytes.
+    pub format: Format,
+
+    /// Whether to print hex digits in upper case.
+    pub upper: bool,
+
+    /// Whether to print results as JSON.
+    pub json: bool,
+
+    /// With `Source::Binary`, where to start reading.
+    pub offset: u64,
+
+    /// With `Source::Binary`, how many bytes to read at most.
+    pub length: Option<u64>,
+
+    /// Maximum number of values to decode.
+    pub count: Option<usize>,
+
+    /// Whether to print a walkthrough of the encoding or decoding.
+    pub explain: bool,
+
+    /// Width of the values in bits, between 1 and 128.
+    pub bits: u32,
+
+    /// Whether to use arbitrary-precision integers.
+    pub big: bool,
+
+    /// Number of bytes to pad the encoding to.
+    pub pad: Option<usize>,
+
+    /// Whether to reject non-minimal encodings.
+    pub strict: bool,
+
+    /// Whether to print the encodings of all inputs as one byte stream.
+    pub concat: bool,
+}
+
+impl Args {
+    /// Whether to decode a sequence of values, printing offsets and lengths, rather than a single
+    /// value.
+    pub fn sequence(&self) -> bool {
+        self.all || self.count.is_some()
+    }
+
+    /// Maximum number of values to decode, `None` for no limit.
+    pub fn max_values(&self) -> Option<usize> {
+        match self.count {
+            Some(count) => Some(count),
+            None if self.all => None,
+            None => Some(1),
+        }
+    }
+}
+
+/// An invalid invocation. Printed together with the usage text of `cmd` (or the general usage
+/// text when `cmd` is `None`).
+#[derive(Debug)]
+pub struct UsageError {
+    pub cmd: Option<Cmd>,
+    pub msg: String,
+}
+
+impl fmt::Display for UsageError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        self.msg.fmt(f)
+    }
+}
+
+fn usage_error(cmd: Option<Cmd>, msg: String) -> UsageError {
+    UsageError { cmd, msg }
+}
+
+/// Whether `arg` should be parsed as an option. Negative numbers and expressions like `-5`,
+/// `-(1 << 7)` or `-i64::MAX` are arguments.
+fn is_option(arg: &str) -> bool {
+    arg.len() > 1
+        && arg.starts_with('-')
+        && !arg[1..].starts_with(|c: char| c.is_ascii_digit() || c == '(' || c == '~')
+        && !arg.contains("::")
+}
+
+pub fn parse<I>(args: I) -> Result<Invocation, UsageError>
+where
+    I: IntoIterator<Item = String>,
+{
+    let mut args = args.into_iter();
+
+    let cmd_word = match args.next() {
+        Some(word) => word,
+        None => return Ok(Invocation::Empty),
+    };
+
+    let cmd = match cmd_word.as_str() {
+        "-h" | "--help" => return Ok(Invocation::Help(None)),
+        "-V" | "--version" => return Ok(Invocation::Version),
+        "help" => {
+            return match args.next() {
+                None => Ok(Invocation::Help(None)),
+                Some(name) => match Cmd::from_name(&name) {
+                    Some(cmd) => Ok(Invocation::Help(Some(cmd))),
+                    None => Err(usage_error(None, format!("Unrecognized command: {}", name))),
+                },
+            };
+        }
+        name => match Cmd::from_name(name) {
+            Some(cmd) => cmd,
+            None => return Err(usage_error(None, format!("Unrecognized command: {}", name))),
+        },
+    };
+
+    let mut positionals: Vec<String> = vec![];
+    let mut only_positionals = false;
+    let mut input_path: Option<String> = None;
+    let mut binary_path: Option<String> = None;
+
+    // `source` is set after all arguments are parsed.
+    let mut parsed = Args {
+        cmd,
+        source: Source::Stdin,
+        keep_going: false,
+        all: false,
+        dump: false,
+        format: Format::default(),
+        upper: false,
+        json: false,
+        offset: 0,
+        length: None,
+        count: None,
+        explain: false,
+        bits: 128,
+        big: false,
+        pad: None,
+        strict: false,
+        concat: false,
+    };
+    let mut pad_max = false;
+
+    while let Some(arg) = args.next() {
+        if only_positionals || !is_option(&arg) {
+            positionals.push(arg);
+            continue;
+        }
+
+        if arg == "--" {
+            only_positionals = true;
+            continue;
+        }
+
+        let (name, inline_value) = match arg.split_once('=') {
+            Some((name, value)) if arg.starts_with("--") => (name, Some(value.to_owned())),
+            _ => (arg.as_str(), None),
+        };
+
+        let spec = match OPTIONS
+            .iter()
+            .find(|spec| spec.matches(name) && spec.applies_to(cmd))
+        {
+            Some(spec) => spec,
+            None => {
+                return Err(usage_error(
+                    Some(cmd),
+                    format!("Unrecognized option for {}: {}", cmd.short_name(), name),
+                ))
+            }
+        };
+
+        let value = match (spec.value, inline_value) {
+            (None, None) => None,
+            (None, Some(_)) => {
+                return Err(usage_error(
+                    Some(cmd),
+                    format!("Option --{} does not take a value", spec.long),
+                ))
+            }
+            (Some(_), Some(value)) => Some(value),
+            (Some(value_name), None) => match args.next() {
+                Some(value) => Some(value),
+                None => {
+                    return Err(usage_error(
+                        Some(cmd),
+                        format!("Option --{} requires a value {}", spec.long, value_name),
+                    ))
+                }
+            },
+        };
+
+        match (spec.long, value) {
+            ("help", _) => return Ok(Invocation::Help(Some(cmd))),
+            ("version", _) => return Ok(Invocation::Version),
+            ("input", Some(path)) => input_path = Some(path),
+            ("keep-going", _) => parsed.keep_going = true,
+            ("all", _) => parsed.all = true,
+            ("dump", _) => parsed.dump = true,
+            ("format", Some(name)) => {
+                parsed.format = name.parse().map_err(|msg| usage_error(Some(cmd), msg))?;
+            }
+            ("upper", _) => parsed.upper = true,
+            ("json", _) => parsed.json = true,
+            ("explain", _) => parsed.explain = true,
+            ("binary", Some(path)) => binary_path = Some(path),
+            ("offset", Some(n)) => parsed.offset = parse_number(cmd, spec, &n)?,
+            ("length", Some(n)) => parsed.length = Some(parse_number(cmd, spec, &n)?),
+            ("count", Some(n)) => parsed.count = Some(parse_number(cmd, spec, &n)?),
+            ("big", _) => parsed.big = true,
+            ("strict", _) => parsed.strict = true,
+            ("concat", _) => parsed.concat = true,
+            ("pad", Some(n)) if n == "max" => pad_max = true,
+            ("pad", Some(n)) => {
+                pad_max = false;
+                parsed.pad = Some(parse_number(cmd, spec, &n)?);
+            }
+            ("bits", Some(n)) => {
+                parsed.bits = parse_number(cmd, spec, &n)?;
+                if !(1..=128).contains(&parsed.bits) {
+                    return Err(usage_error(
+                        Some(cmd),
+                        format!("Invalid value for --bits: {:?}, expected 1 to 128", n),
+                    ));
+                }
+            }
+            (other, _) => unreachable!("Option --{} not handled", other),
+        }
+    }
+
+    if parsed.big && parsed.bits != 128 {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --big and --bits can't be used together".to_owned(),
+        ));
+    }
+
+    let max_len = super::max_encoded_len(parsed.bits);
+    if pad_max {
+        parsed.pad = Some(max_len);
+    }
+    match parsed.pad {
+        Some(0) => {
+            return Err(usage_error(
+                Some(cmd),
+                "Invalid value for --pad: values take at least 1 byte".to_owned(),
+            ))
+        }
+        Some(pad) if pad > max_len => {
+            return Err(usage_error(
+                Some(cmd),
+                format!(
+                    "Invalid value for --pad: {}-bit values take at most {} bytes",
+                    parsed.bits, max_len
+                ),
+            ))
+        }
+        _ => {}
+    }
+
+    if parsed.pad.is_some() && (parsed.big || parsed.explain) {
+        return Err(usage_error(
+            Some(cmd),
+            "Option --pad can't be used together with --big or --explain".to_owned(),
+        ));
+    }
+
+    if parsed.big && parsed.strict {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --big and --strict can't be used together".to_owned(),
+        ));
+    }
+
+    if parsed.big && parsed.explain {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --big and --explain can't be used together".to_owned(),
+        ));
+    }
+
+    if parsed.concat && parsed.json {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --concat and --json can't be used together".to_owned(),
+        ));
+    }
+
+    // A stream with the bad inputs left out would decode to the wrong values.
+    if parsed.concat && parsed.keep_going {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --concat and --keep-going can't be used together".to_owned(),
+        ));
+    }
+
+    if parsed.json && parsed.explain {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --json and --explain can't be used together".to_owned(),
+        ));
+    }
+
+    if binary_path.is_none() && (parsed.offset != 0 || parsed.length.is_some()) {
+        return Err(usage_error(
+            Some(cmd),
+            "Options --offset and --length can only be used with --binary".to_owned(),
+        ));
+    }
+
+    parsed.source = match (binary_path, input_path) {
+        (Some(_), Some(_)) => {
+            return Err(usage_error(
+                Some(cmd),
+                "Options --binary and --input can't be used together".to_owned(),
+            ))
+        }
+        (Some(path), None) => {
+            if let Some(arg) = positionals.first() {
+                return Err(usage_error(
+                    Some(cmd),
+                    format!("Argument {} can't be used together with --binary", arg),
+                ));
+            }
+            Source::Binary(PathBuf::from(path))
+        }
+        (None, Some(path)) => {
+            if let Some(arg) = positionals.first() {
+                return Err(usage_error(
+                    Some(cmd),
+                    format!("Argument {} can't be used together with --input", arg),
+                ));
+            }
+            if path == "-" {
+                Source::Stdin
+            } else {
+                Source::File(PathBuf::from(path))
+            }
+        }
+        (None, None) if positionals.is_empty() => Source::Stdin,
+        (None, None) if positionals.len() == 1 && positionals[0] == "-" => Source::Stdin,
+        (None, None) => Source::Args(positionals),
+    };
+
+    Ok(Invocation::Run(parsed))
+}
+
+/// Parses the value of a numeric option, written like the numbers given to `eu`.
+fn parse_number<T>(cmd: Cmd, spec: &OptSpec, value: &str) -> Result<T, UsageError>
+where
+    T: TryFrom<u128>,
+{
+    super::literal::parse_unsigned(value)
+        .ok()
+        .and_then(|n| T::try_from(n).ok())
+        .ok_or_else(|| {
+            usage_error(
+                Some(cmd),
+                format!("Invalid value for --{}: {:?}", spec.long, value),
+            )
+        })
+}
+
+/// The error for a command run without arguments where inputs can't be read from standard
+/// input.
+pub fn missing_argument(cmd: Cmd) -> UsageError {
+    usage_error(
+        Some(cmd),
+        format!("Argument to command {} is missing.", cmd.short_name()),
+    )
+}
+
+pub fn version() -> String {
+    format!("leb128 {}", env!("CARGO_PKG_VERSION"))
+}
+
+/// General usage text, listing all commands.
+pub fn usage() -> String {
+    let mut s = String::new();
+    s.push_str("Encode and decode LEB128 numbers.\n\n");
+    s.push_str("Usage:\n");
+    s.push_str("    leb128                                 Start an interactive session\n");
+    s.push_str("    leb128 <COMMAND> [OPTIONS] <ARGS>...   Run a single command\n");
+    s.push_str("    leb128 <COMMAND> [OPTIONS] [-]         Run a command on each line of stdin\n");
+    s.push_str("    leb128 <COMMAND> [OPTIONS] -i <PATH>   Run a command on each line of a file\n");
+    s.push_str("\nCommands:\n");
+    for cmd in CMDS {
+        let names = format!("{}, {}", cmd.short_name(), cmd.long_name());
+        s.push_str(&format!("    {:<22} {}\n", names, cmd.summary()));
+    }
+    s.push_str(&format!("    {:<22} {}\n", "help [COMMAND]", "Print help"));
+    s.push_str("\nOptions:\n");
+    s.push_str(&format!("    {:<22} {}\n", "-h, --help", "Print help"));
+    s.push_str(&format!(
+        "    {:<22} {}\n",
+        "-V, --version", "Print version"
+    ));
+    s.push_str("\nExit status:\n");
+    s.push_str("    0    Success\n");
+    s.push_str("    1    Invalid command line\n");
+    s.push_str("    2    Invalid input (bad number or hex byte)\n");
+    s.push_str("    3    Truncated input\n");
+    s.push_str("    4    Overflow\n");
+    s.push_str("    5    I/O error, including a terminal the REPL can't use\n");
+    s.push_str("    6    Non-canonical input rejected by --strict\n");
+    s.push_str("\nRun `leb128 <COMMAND> --help` for more information on a command.\n");
+    s
+}
+
+/// Usage text of a single command, listing its options.
+pub fn cmd_usage(cmd: Cmd) -> String {
+    let mut s = String::new();
+    s.push_str(&format!("{}.\n\n", cmd.summary()));
+    s.push_str(&format!(
+        "Usage: leb128 {} [OPTIONS] {}\n",
+        cmd.short_name(),
+        cmd.arg_name()
+    ));
+    if cmd.is_encode() {
+        s.push_str(&format!(
+            "       leb128 {} --big [OPTIONS] <NUMBER>...\n",
+            cmd.short_name()
+        ));
+    }
+    s.push_str(&format!(
+        "Aliases: {}, {}\n",
+        cmd.short_name(),
+        cmd.long_name()
+    ));
+    s.push_str("\nArguments:\n");
+    s.push_str(&format!("    {:<22} {}\n", cmd.arg_name(), cmd.arg_help()));
+    s.push_str(&format!(
+        "    {:<22} {}\n",
+        "",
+        "Without arguments, or with `-`, inputs are read from standard input, one per line. \
+         Arguments are never file paths, use --input to read a file"
+    ));
+    s.push_str("\nOptions:\n");
+    for spec in OPTIONS.iter().filter(|spec| spec.applies_to(cmd)) {
+        let mut names = match spec.short {
+            Some(short) => format!("-{}, --{}", short, spec.long),
+            None => format!("    --{}", spec.long),
+        };
+        if let Some(value) = spec.value {
+            names.push(' ');
+            names.push_str(value);
+        }
+        s.push_str(&format!("    {:<22} {}\n", names, spec.help));
+    }
+    s.push_str("\nBit widths:\n");
+    if cmd.is_encode() {
+        s.push_str(
+            "    With --bits N, values that don't fit in N bits are rejected. --pad max pads\n",
+        );
+        s.push_str("    to ceil(N / 7) bytes, e.g. 5 bytes for 32-bit and 10 bytes for 64-bit\n");
+        s.push_str("    values, as in relocatable WebAssembly object files.\n");
+    } else {
+        s.push_str(
+            "    With --bits N, encodings are checked as the WebAssembly spec does: they are\n",
+        );
+        s.push_str(
+            "    rejected when longer than ceil(N / 7) bytes, or when the bits of the last\n",
+        );
+        s.push_str(
+            "    byte beyond N are not zero (unsigned) or copies of the sign bit (signed).\n",
+        );
+    }
+    if cmd.is_encode() {
+        s.push_str("\nExpressions:\n");
+        s.push_str(
+            "    Numbers are decimal, or hexadecimal, octal or binary with a 0x, 0o or 0b\n",
+        );
+        s.push_str(
+            "    prefix, and may contain `_` separators. The operators are + - * / % << >> & | ^\n",
+        );
+        s.push_str(
+            "    and unary - and ~ with Rust's precedence, and ** (power) binding tightest.\n",
+        );
+        s.push_str(
+            "    Constants like i64::MIN or u32::MAX name the limits of the integer types.\n",
+        );
+        s.push_str("    Arithmetic is checked: results that don't fit in 128 bits are rejected.\n");
+        s.push_str("\nFormats:\n");
+        s.push_str(&output::formats_help());
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_args(args: &[&str]) -> Result<Args, String> {
+        match parse(args.iter().map(|arg| arg.to_string())) {
+            Ok(Invocation::Run(args)) => Ok(args),
+            Ok(other) => panic!("Unexpected: {:?}", other),
+            Err(err) => Err(err.msg),
+        }
+    }
+
+    fn invocation(args: &[&str]) -> Invocation {
+        parse(args.iter().map(|arg| arg.to_string())).expect("Should parse")
+    }
+
+    #[test]
+    fn test_commands() {
+        for (name, cmd) in [
+            ("es", Cmd::EncodeSigned),
+            ("encode-signed", Cmd::EncodeSigned),
+            ("eu", Cmd::EncodeUnsigned),
+            ("encode-unsigned", Cmd::EncodeUnsigned),
+            ("ds", Cmd::DecodeSigned),
+            ("decode-signed", Cmd::DecodeSigned),
+            ("du", Cmd::DecodeUnsigned),
+            ("decode-unsigned", Cmd::DecodeUnsigned),
+        ] {
+            assert_eq!(parse_args(&[name, "00"]).unwrap().cmd, cmd, "{}", name);
+        }
+        assert_eq!(
+            parse_args(&["enc", "1"]).unwrap_err(),
+            "Unrecognized command: enc"
+        );
+
+        assert!(matches!(invocation(&[]), Invocation::Empty));
+        assert!(matches!(invocation(&["--help"]), Invocation::Help(None)));
+        assert!(matches!(invocation(&["help"]), Invocation::Help(None)));
+        assert!(matches!(
+            invocation(&["help", "du"]),
+            Invocation::Help(Some(Cmd::DecodeUnsigned))
+        ));
+        assert!(matches!(
+            invocation(&["eu", "-h"]),
+            Invocation::Help(Some(Cmd::EncodeUnsigned))
+        ));
+        assert!(matches!(invocation(&["-V"]), Invocation::Version));
+        assert!(matches!(
+            invocation(&["ds", "--version"]),
+            Invocation::Version
+        ));
+    }
+
+    #[test]
+    fn test_is_option() {
+        for arg in ["-5", "-(1)", "-i64::MAX", "-~0", "-0x80", "-", "5"] {
+            assert!(!is_option(arg), "{}", arg);
+        }
+        for arg in ["-h", "--help", "--help=x", "--bits=32", "--"] {
+            assert!(is_option(arg), "{}", arg);
+        }
+
+        let args = parse_args(&["es", "-5", "-(1)", "-i64::MAX"]).unwrap();
+        assert_eq!(
+            args.source,
+            Source::Args(vec!["-5".into(), "-(1)".into(), "-i64::MAX".into()])
+        );
+    }
+
+    #[test]
+    fn test_option_values() {
+        for args in [
+            &["du", "--bits=32", "--count=0x10", "00"][..],
+            &["du", "--bits", "32", "--count", "0x10", "00"],
+            &["du", "--bits", "32", "-n", "16", "00"],
+        ] {
+            let args = parse_args(args).unwrap();
+            assert_eq!((args.bits, args.count), (32, Some(16)));
+        }
+        assert_eq!(
+            parse_args(&["du", "00", "--bits"]).unwrap_err(),
+            "Option --bits requires a value <N>"
+        );
+        assert_eq!(
+            parse_args(&["du", "--bits=0", "00"]).unwrap_err(),
+            "Invalid value for --bits: \"0\", expected 1 to 128"
+        );
+        assert_eq!(
+            parse_args(&["du", "--count=x", "00"]).unwrap_err(),
+            "Invalid value for --count: \"x\""
+        );
+        assert_eq!(
+            parse_args(&["du", "--pad=3", "00"]).unwrap_err(),
+            "Unrecognized option for du: --pad"
+        );
+        assert_eq!(
+            parse_args(&["eu", "--pad=0", "1"]).unwrap_err(),
+            "Invalid value for --pad: values take at least 1 byte"
+        );
+        assert_eq!(
+            parse_args(&["eu", "--bits", "8", "--pad", "3", "1"]).unwrap_err(),
+            "Invalid value for --pad: 8-bit values take at most 2 bytes"
+        );
+        assert_eq!(parse_args(&["eu", "--pad=max", "1"]).unwrap().pad, Some(19));
+        assert_eq!(
+            parse_args(&["eu", "--bits", "32", "--pad", "max", "1"])
+                .unwrap()
+                .pad,
+            Some(5)
+        );
+
+        assert_eq!(
+            parse_args(&["du", "--help=yes", "00"]).unwrap_err(),
+            "Option --help does not take a value"
+        );
+        assert_eq!(
+            parse_args(&["du", "--json=yes", "00"]).unwrap_err(),
+            "Option --json does not take a value"
+        );
+        assert!(parse_args(&["du", "-j", "00"]).unwrap().json);
+        assert_eq!(
+            parse_args(&["du", "--hex", "00"]).unwrap_err(),
+            "Unrecognized option for du: --hex"
+        );
+        assert_eq!(
+            parse_args(&["du", "-hx", "00"]).unwrap_err(),
+            "Unrecognized option for du: -hx"
+        );
+    }
+
+    #[test]
+    fn test_conflicts() {
+        for (args, msg) in [
+            (
+                &["eu", "--big", "--bits", "32", "1"][..],
+                "Options --big and --bits can't be used together",
+            ),
+            (
+                &["du", "--big", "--strict", "00"],
+                "Options --big and --strict can't be used together",
+            ),
+            (
+                &["du", "--big", "--explain", "00"],
+                "Options --big and --explain can't be used together",
+            ),
+            (
+                &["eu", "--big", "--pad", "3", "1"],
+                "Option --pad can't be used together with --big or --explain",
+            ),
+            (
+                &["eu", "--concat", "--json", "1"],
+                "Options --concat and --json can't be used together",
+            ),
+            (
+                &["eu", "--concat", "--keep-going", "1"],
+                "Options --concat and --keep-going can't be used together",
+            ),
+            (
+                &["du", "--json", "--explain", "00"],
+                "Options --json and --explain can't be used together",
+            ),
+            (
+                &["du", "--offset", "1", "00"],
+                "Options --offset and --length can only be used with --binary",
+            ),
+            (
+                &["du", "--length", "1", "00"],
+                "Options --offset and --length can only be used with --binary",
+            ),
+            (
+                &["du", "--binary", "a.bin", "--input", "a.txt"],
+                "Options --binary and --input can't be used together",
+            ),
+            (
+                &["du", "--binary", "a.bin", "00"],
+                "Argument 00 can't be used together with --binary",
+            ),
+        ] {
+            assert_eq!(parse_args(args).unwrap_err(), msg, "{:?}", args);
+        }
+    }
+
+    #[test]
+    fn test_source() {
+        assert_eq!(
+            parse_args(&["du", "e5", "8e 26"]).unwrap().source,
+            Source::Args(vec!["e5".into(), "8e 26".into()])
+        );
+        assert_eq!(
+            parse_args(&["eu", "1", "2 + 3"]).unwrap().source,
+            Source::Args(vec!["1".into(), "2 + 3".into()])
+        );
+        assert_eq!(
+            parse_args(&["es", "--", "-h"]).unwrap().source,
+            Source::Args(vec!["-h".into()])
+        );
+        assert_eq!(parse_args(&["du"]).unwrap().source, Source::Stdin);
+        assert_eq!(parse_args(&["du", "-"]).unwrap().source, Source::Stdin);
+        assert_eq!(
+            parse_args(&["du", "--input", "-"]).unwrap().source,
+            Source::Stdin
+        );
+        assert_eq!(
+            parse_args(&["du", "--input", "a.txt"]).unwrap().source,
+            Source::File(PathBuf::from("a.txt"))
+        );
+        // A file name given as an argument is an input like any other.
+        assert_eq!(
+            parse_args(&["du", "a.txt"]).unwrap().source,
+            Source::Args(vec!["a.txt".into()])
+        );
+
+        let args = parse_args(&["du", "-i", "a.txt", "--keep-going"]).unwrap();
+        assert_eq!(args.source, Source::File(PathBuf::from("a.txt")));
+        assert!(args.keep_going);
+
+        assert_eq!(
+            parse_args(&["du", "--input", "a.txt", "00"]).unwrap_err(),
+            "Argument 00 can't be used together with --input"
+        );
+        assert_eq!(
+            parse_args(&["du", "--input"]).unwrap_err(),
+            "Option --input requires a value <PATH>"
+        );
+        assert_eq!(
+            parse_args(&["du", "--binary", "a.bin", "--offset=4"])
+                .unwrap()
+                .source,
+            Source::Binary(PathBuf::from("a.bin"))
+        );
+    }
+}
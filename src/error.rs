@@ -0,0 +1,265 @@
+//! Errors reported by the command line tool.
+//!
+//! Every error is printed as a single `error: ...` line and maps to its own exit code, so
+//! scripts can tell the failures apart without parsing the message:
+//!
+//! | Code | Meaning                                             |
+//! |------|-----------------------------------------------------|
+//! | 0    | Success                                             |
+//! | 1    | Invalid command line (unknown command or option)    |
+//! | 2    | Invalid input (bad number or hex byte)              |
+//! | 3    | Truncated input (a byte sets the continuation bit   |
+//! |      | but the input ends)                                 |
+//! | 4    | Overflow (the value doesn't fit in 128 bits, or in  |
+//! |      | the width given with `--bits`)                      |
+//! | 5    | I/O error                                           |
+//! | 6    | Non-canonical input rejected by `--strict`          |
+
+use super::*;
+
+use std::fmt;
+use std::ops::Range;
+
+pub const EXIT_USAGE: i32 = 1;
+
+#[derive(Debug)]
+pub enum Error {
+    /// The input could not be parsed as a number or as hex bytes.
+    Parse(String),
+
+    /// The input ended in the middle of a LEB128 value.
+    Truncated(String),
+
+    /// The value being decoded is larger than can be represented.
+    Overflow(String),
+
+    /// With `--strict`, the input encodes a value with more bytes than necessary.
+    NonCanonical(String),
+
+    /// There was an I/O error, e.g. while writing the output.
+    Io(io::Error),
+
+    /// An error caused by a specific part of the input. Printed with the input and a caret
+    /// pointing at the offending part.
+    Located(Box<Error>, Location),
+}
+
+/// A part of the input to point at in diagnostics.
+#[derive(Debug)]
+pub struct Location {
+    pub input: String,
+    /// Byte range of the offending part in `input`.
+    pub span: Range<usize>,
+}
+
+impl Error {
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Error::Parse(_) => 2,
+            Error::Truncated(_) => 3,
+            Error::Overflow(_) => 4,
+            Error::Io(_) => 5,
+            Error::NonCanonical(_) => 6,
+            Error::Located(error, _) => error.exit_code(),
+        }
+    }
+
+    /// Name of the error in `--json` output. Part of the stable output schema.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Error::Parse(_) => "parse",
+            Error::Truncated(_) => "truncated",
+            Error::Overflow(_) => "overflow",
+            Error::Io(_) => "io",
+            Error::NonCanonical(_) => "non-canonical",
+            Error::Located(error, _) => error.kind(),
+        }
+    }
+
+    /// The error message without the location.
+    pub fn message(&self) -> String {
+        match self {
+            Error::Located(error, _) => error.message(),
+            error => error.to_string(),
+        }
+    }
+
+    /// The location of the offending part of the input, if known.
+    pub fn location(&self) -> Option<&Location> {
+        match self {
+            Error::Located(_, location) => Some(location),
+            _ => None,
+        }
+    }
+
+    /// Attaches the location of the offending part of the input to the error.
+    pub fn at(self, input: &str, span: Range<usize>) -> Error {
+        Error::Located(
+            Box::new(self),
+            Location {
+                input: input.to_owned(),
+                span,
+            },
+        )
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Self {
+        Error::Io(e)
+    }
+}
+
+impl From<read::Error> for Error {
+    fn from(e: read::Error) -> Self {
+        match e {
+            read::Error::IoError(e) if e.kind() == io::ErrorKind::UnexpectedEof => {
+                Error::Truncated("Input ends in the middle of a LEB128 value".to_owned())
+            }
+            read::Error::IoError(e) => Error::Io(e),
+            read::Error::Overflow => Error::Overflow(read::Error::Overflow.to_string()),
+            e @ read::Error::NonCanonical { .. } => Error::NonCanonical(e.to_string()),
+        }
+    }
+}
+
+impl From<write::Error> for Error {
+    fn from(e: write::Error) -> Self {
+        match e {
+            write::Error::IoError(e) => Error::Io(e),
+            e => Error::Overflow(e.to_string()),
+        }
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Error::Parse(msg)
+            | Error::Truncated(msg)
+            | Error::Overflow(msg)
+            | Error::NonCanonical(msg) => msg.fmt(f),
+            Error::Io(e) => write!(f, "I/O error: {}", e),
+            Error::Located(error, location) => write!(f, "{}\n{}", error, location),
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    /// Shows the line of the input containing the start of the span, with carets under the span.
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let start = self.span.start.min(self.input.len());
+        let line_start = self.input[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = self.input[start..]
+            .find('\n')
+            .map_or(self.input.len(), |i| start + i);
+        let line = &self.input[line_start..line_end];
+
+        // Keep tabs in the padding so the caret lines up with the echoed input.
+        let padding: String = self.input[line_start..start]
+            .chars()
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect();
+
+        let end = self.span.end.clamp(start, line_end);
+        let width = self.input[start..end].chars().count().max(1);
+
+        writeln!(f, "    {}", line)?;
+        write!(f, "    {}{}", padding, "^".repeat(width))
+    }
+}
+
+/// The message and the span of the location of the error in `result`, for comparing in tests.
+#[cfg(test)]
+pub fn message_and_span<T>(result: Result<T, Error>) -> (String, Option<Range<usize>>) {
+    match result {
+        Ok(_) => panic!("Should fail"),
+        Err(e) => (e.message(), e.location().map(|l| l.span.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(input: &str, span: Range<usize>) -> String {
+        Location {
+            input: input.to_owned(),
+            span,
+        }
+        .to_string()
+    }
+
+    #[test]
+    fn test_location() {
+        assert_eq!(show("12a4", 2..3), "    12a4\n      ^");
+        assert_eq!(show("e5 8e zz", 6..8), "    e5 8e zz\n          ^^");
+        // Tabs are kept in the padding, other characters become spaces.
+        assert_eq!(show("e5\t8e\tzz", 6..8), "    e5\t8e\tzz\n      \t  \t^^");
+        assert_eq!(show("5é", 1..3), "    5é\n     ^");
+        assert_eq!(show("é x", 3..4), "    é x\n      ^");
+    }
+
+    #[test]
+    fn test_location_multi_line() {
+        // Only the line with the start of the span is shown.
+        assert_eq!(show("00\n01 zz\n02", 6..8), "    01 zz\n       ^^");
+        assert_eq!(show("00\n01 zz\n02", 0..1), "    00\n    ^");
+        assert_eq!(show("00\n01 zz\n02", 9..11), "    02\n    ^^");
+        // Spans running into the next line stop at the end of the line.
+        assert_eq!(show("ab\ncd", 1..4), "    ab\n     ^");
+    }
+
+    #[test]
+    fn test_location_empty_spans() {
+        // Zero-width spans and spans at or past the end still show one caret.
+        assert_eq!(show("abc", 1..1), "    abc\n     ^");
+        assert_eq!(show("e5 8e", 5..5), "    e5 8e\n         ^");
+        assert_eq!(show("12", 5..7), "    12\n      ^");
+        assert_eq!(show("", 0..0), "    \n    ^");
+    }
+
+    #[test]
+    fn test_located_error() {
+        let err = Error::Parse("Invalid hex byte".to_owned()).at("e5 zz", 3..5);
+        assert_eq!(err.to_string(), "Invalid hex byte\n    e5 zz\n       ^^");
+        assert_eq!(err.message(), "Invalid hex byte");
+        assert_eq!(err.location().unwrap().span, 3..5);
+        assert_eq!(err.exit_code(), 2);
+        assert_eq!(err.kind(), "parse");
+
+        let err = Error::Truncated("Input ends".to_owned());
+        assert_eq!(err.message(), "Input ends");
+        assert!(err.location().is_none());
+        assert_eq!(err.kind(), "truncated");
+    }
+
+    #[test]
+    fn test_exit_codes() {
+        let errors = [
+            (Error::Parse(String::new()), 2),
+            (Error::Truncated(String::new()), 3),
+            (Error::Overflow(String::new()), 4),
+            (Error::Io(io::ErrorKind::Other.into()), 5),
+            (Error::NonCanonical(String::new()), 6),
+            (Error::Truncated(String::new()).at("80", 0..2), 3),
+        ];
+        for (err, code) in errors {
+            assert_eq!(err.exit_code(), code, "{:?}", err);
+        }
+
+        let eof = read::Error::IoError(io::ErrorKind::UnexpectedEof.into());
+        assert_eq!(Error::from(eof).exit_code(), 3);
+        let io = read::Error::IoError(io::ErrorKind::BrokenPipe.into());
+        assert_eq!(Error::from(io).exit_code(), 5);
+        assert_eq!(Error::from(read::Error::Overflow).exit_code(), 4);
+        let non_canonical = read::Error::NonCanonical { redundant: 1 };
+        assert_eq!(Error::from(non_canonical).exit_code(), 6);
+
+        assert_eq!(Error::from(write::Error::Overflow).exit_code(), 4);
+        let padding = write::Error::PaddingTooShort { needed: 3 };
+        assert_eq!(Error::from(padding).exit_code(), 4);
+        let io = write::Error::IoError(io::ErrorKind::WriteZero.into());
+        assert_eq!(Error::from(io).exit_code(), 5);
+    }
+}
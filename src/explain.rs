@@ -0,0 +1,407 @@
+//! `--explain`: step by step walkthroughs of encoding and decoding, following `read` and `write`
+//! one byte at a time.
+
+use super::*;
+
+use std::fmt::Write;
+
+/// `byte` with its continuation bit separated from the payload, e.g. `1 1100101`.
+fn byte_bits(byte: u8) -> String {
+    format!("{} {:07b}", byte >> 7, low_bits_of_byte(byte))
+}
+
+/// Whether `byte` can be the next byte of an unsigned value with `remaining` bits of the value
+/// left, the check of `read::unsigned_bits`.
+fn valid_unsigned_byte(byte: u8, remaining: u32) -> bool {
+    remaining > 7 || (remaining > 0 && byte >> remaining == 0)
+}
+
+/// Whether `byte` can be the next byte of a signed value with `remaining` bits of the value left,
+/// the check of `read::signed_bits`. The bits of the last byte beyond the remaining ones must be
+/// copies of the sign bit.
+fn valid_signed_byte(byte: u8, remaining: u32) -> bool {
+    if remaining > 7 {
+        return true;
+    }
+    if remaining == 0 {
+        return false;
+    }
+    let high_bits = byte >> (remaining - 1);
+    high_bits == 0 || high_bits == 0x7f >> (remaining - 1)
+}
+
+/// Formats rows as a table with aligned columns, indented by two spaces.
+fn table(s: &mut String, header: &[&str], rows: &[Vec<String>]) {
+    let mut widths: Vec<usize> = header.iter().map(|h| h.len()).collect();
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.len());
+        }
+    }
+
+    let header: Vec<String> = header.iter().map(|h| h.to_string()).collect();
+    for row in std::iter::once(&header).chain(rows) {
+        let mut line = String::from(" ");
+        for (width, cell) in widths.iter().zip(row) {
+            let _ = write!(line, " {:<width$}", cell, width = width);
+        }
+        let _ = writeln!(s, "{}", line.trim_end());
+    }
+}
+
+/// Explains decoding the LEB128 values in `bytes`, following `read::unsigned` and `read::signed`.
+/// `base` is the offset of `bytes` in the input, and `bits` the width of the values. Explains at
+/// most `max_values` values, and stops at the first error.
+pub fn decode(
+    bytes: &[u8],
+    base: usize,
+    signed: bool,
+    bits: u32,
+    max_values: Option<usize>,
+) -> String {
+    let mut s = String::new();
+    let mut start = 0;
+    let mut n_values = 0;
+
+    while start < bytes.len() && max_values.is_none_or(|max| n_values < max) {
+        match decode_value(&mut s, &bytes[start..], base + start, signed, bits) {
+            Some(len) => start += len,
+            None => break,
+        }
+        n_values += 1;
+    }
+
+    s
+}
+
+/// Explains decoding one value from `bytes`, which start at `offset` of the input. Returns the
+/// length of the value, or `None` if it can't be decoded.
+fn decode_value(
+    s: &mut String,
+    bytes: &[u8],
+    offset: usize,
+    signed: bool,
+    bits: u32,
+) -> Option<usize> {
+    let kind = if signed { "signed" } else { "unsigned" };
+    let _ = writeln!(
+        s,
+        "Decoding {}-bit {} LEB128 value at offset {}:",
+        bits, kind, offset
+    );
+
+    let mut rows = vec![];
+    let mut result: u128 = 0;
+    let mut shift = 0;
+    let mut notes = vec![];
+    let mut len = None;
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        let byte_offset = offset + i;
+
+        // The overflow checks of `read::unsigned_bits` and `read::signed_bits`.
+        let remaining = bits - shift;
+        let valid = if signed {
+            valid_signed_byte(byte, remaining)
+        } else {
+            valid_unsigned_byte(byte, remaining)
+        };
+        if !valid {
+            notes.push(if byte & CONTINUATION_BIT != 0 {
+                format!(
+                    "Byte at offset {} ({:02x}) sets the continuation bit but {}-bit values are at \
+                     most {} bytes long.",
+                    byte_offset,
+                    byte,
+                    bits,
+                    max_encoded_len(bits)
+                )
+            } else if signed {
+                format!(
+                    "Byte at offset {} ({:02x}) holds the last {} bits of the value, the bits \
+                     above them must be copies of the sign bit.",
+                    byte_offset, byte, remaining
+                )
+            } else {
+                format!(
+                    "Byte at offset {} ({:02x}) holds the last {} bits of the value, the bits \
+                     above them must be zero.",
+                    byte_offset, byte, remaining
+                )
+            });
+            break;
+        }
+
+        let payload = low_bits_of_byte(byte);
+        result |= (payload as u128) << shift;
+        let continues = byte & CONTINUATION_BIT != 0;
+
+        rows.push(vec![
+            byte_offset.to_string(),
+            format!("{:02x}", byte),
+            byte_bits(byte),
+            if continues { "set" } else { "clear" }.to_owned(),
+            format!("{:07b}", payload),
+            shift.to_string(),
+            if signed {
+                (result as i128).to_string()
+            } else {
+                result.to_string()
+            },
+        ]);
+
+        shift += 7;
+
+        if continues {
+            continue;
+        }
+
+        len = Some(i + 1);
+        notes.push(format!(
+            "Continuation bit (0x{:02x}) of the byte at offset {} is clear, the value ends here.",
+            CONTINUATION_BIT, byte_offset
+        ));
+
+        if signed {
+            let sign_set = byte & SIGN_BIT == SIGN_BIT;
+            notes.push(if sign_set && shift < 128 {
+                format!(
+                    "Sign bit (0x{:02x}) of the byte at offset {} is set: sign extend from bit {}, \
+                     result | (!0 << {}) = {}",
+                    SIGN_BIT,
+                    byte_offset,
+                    shift,
+                    shift,
+                    (result as i128) | (!0 << shift)
+                )
+            } else if sign_set {
+                format!(
+                    "Sign bit (0x{:02x}) of the byte at offset {} is set but all 128 bits are \
+                     filled, no sign extension needed.",
+                    SIGN_BIT, byte_offset
+                )
+            } else {
+                format!(
+                    "Sign bit (0x{:02x}) of the byte at offset {} is clear: no sign extension, the \
+                     value is positive.",
+                    SIGN_BIT, byte_offset
+                )
+            });
+        }
+        break;
+    }
+
+    if len.is_none() && notes.is_empty() {
+        notes.push(format!(
+            "Continuation bit of the byte at offset {} is set but the input ends here.",
+            offset + bytes.len() - 1
+        ));
+    }
+
+    table(
+        s,
+        &[
+            "offset",
+            "hex",
+            "binary",
+            "continuation",
+            "payload",
+            "shift",
+            "result",
+        ],
+        &rows,
+    );
+    for note in notes {
+        let _ = writeln!(s, "  {}", note);
+    }
+
+    len
+}
+
+/// Explains encoding `val` as unsigned LEB128, following `write::unsigned`.
+pub fn encode_unsigned(mut val: u128) -> String {
+    let mut s = String::new();
+    let _ = writeln!(s, "Encoding {} as unsigned LEB128:", val);
+
+    let mut rows = vec![];
+    loop {
+        let low_bits = low_bits_of_byte(val as u8);
+        let rest = val >> 7;
+        let (byte, note) = if rest != 0 {
+            (
+                low_bits | CONTINUATION_BIT,
+                "more to come, continuation bit set",
+            )
+        } else {
+            (low_bits, "last byte")
+        };
+        rows.push(vec![
+            (rows.len() + 1).to_string(),
+            val.to_string(),
+            format!("{:07b}", low_bits),
+            rest.to_string(),
+            byte_bits(byte),
+            format!("{:02x}", byte),
+            note.to_owned(),
+        ]);
+
+        val = rest;
+        if val == 0 {
+            break;
+        }
+    }
+
+    table(
+        &mut s,
+        &[
+            "step",
+            "value",
+            "low 7 bits",
+            "value >> 7",
+            "binary",
+            "byte",
+            "",
+        ],
+        &rows,
+    );
+    s
+}
+
+/// Explains encoding `val` as signed LEB128, following `write::signed`.
+pub fn encode_signed(mut val: i128) -> String {
+    let mut s = String::new();
+    let _ = writeln!(s, "Encoding {} as signed LEB128:", val);
+
+    let mut rows = vec![];
+    loop {
+        let low_bits = low_bits_of_byte(val as u8);
+        let sign_bit = (val as u8 & SIGN_BIT != 0) as u8;
+        // `write::signed` keeps the sign bit for testing whether this is the last byte.
+        let shifted = val >> 6;
+        let done = shifted == 0 || shifted == -1;
+        let (byte, note) = if done {
+            (low_bits, "value >> 6 is all sign bits: last byte")
+        } else {
+            (
+                low_bits | CONTINUATION_BIT,
+                "more to come, continuation bit set",
+            )
+        };
+        rows.push(vec![
+            (rows.len() + 1).to_string(),
+            val.to_string(),
+            format!("{:07b}", low_bits),
+            sign_bit.to_string(),
+            shifted.to_string(),
+            byte_bits(byte),
+            format!("{:02x}", byte),
+            note.to_owned(),
+        ]);
+
+        if done {
+            break;
+        }
+        val = shifted >> 1;
+    }
+
+    table(
+        &mut s,
+        &[
+            "step",
+            "value",
+            "low 7 bits",
+            "sign bit",
+            "value >> 6",
+            "binary",
+            "byte",
+            "",
+        ],
+        &rows,
+    );
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode() {
+        let s = decode(&[0xe5, 0x8e, 0x26], 0, false, 128, None);
+        assert!(
+            s.contains("Decoding 128-bit unsigned LEB128 value at offset 0:"),
+            "{}",
+            s
+        );
+        assert!(
+            s.contains("2      26  0 0100110 clear        0100110 14    624485"),
+            "{}",
+            s
+        );
+
+        let s = decode(&[0x7f], 0, true, 128, None);
+        assert!(s.contains("result | (!0 << 7) = -1"), "{}", s);
+
+        let s = decode(&[0x01, 0x80], 4, false, 128, None);
+        assert!(s.contains("value at offset 5:"), "{}", s);
+        assert!(
+            s.contains("byte at offset 5 is set but the input ends here"),
+            "{}",
+            s
+        );
+
+        let s = decode(&[0x01, 0x02, 0x03], 0, false, 128, Some(2));
+        assert_eq!(s.matches("Decoding").count(), 2);
+
+        let s = decode(&[0x80, 0x02], 0, false, 8, None);
+        assert!(s.contains("the bits above them must be zero"), "{}", s);
+        let s = decode(&[0x80, 0x80], 0, true, 8, None);
+        assert!(s.contains("8-bit values are at most 2 bytes long"), "{}", s);
+    }
+
+    #[test]
+    fn test_valid_byte() {
+        // The checks agree with the readers on the first byte of a value. The zero byte after it
+        // ends values that continue.
+        for bits in 1..=9 {
+            for byte in 0..=u8::MAX {
+                let overflow = matches!(
+                    read::unsigned_bits(&mut &[byte, 0][..], bits),
+                    Err(read::Error::Overflow)
+                );
+                assert_eq!(
+                    valid_unsigned_byte(byte, bits),
+                    !overflow,
+                    "{} {}",
+                    byte,
+                    bits
+                );
+
+                let overflow = matches!(
+                    read::signed_bits(&mut &[byte, 0][..], bits),
+                    Err(read::Error::Overflow)
+                );
+                assert_eq!(
+                    valid_signed_byte(byte, bits),
+                    !overflow,
+                    "{} {}",
+                    byte,
+                    bits
+                );
+            }
+        }
+        assert!(!valid_unsigned_byte(0, 0));
+        assert!(!valid_signed_byte(0, 0));
+    }
+
+    #[test]
+    fn test_encode() {
+        let s = encode_unsigned(624485);
+        assert_eq!(s.lines().count(), 5);
+        assert!(s.contains("e5   more to come"), "{}", s);
+        assert!(s.contains("26   last byte"), "{}", s);
+
+        let s = encode_signed(-123456);
+        assert!(s.contains("78   value >> 6 is all sign bits"), "{}", s);
+    }
+}
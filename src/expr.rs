@@ -0,0 +1,693 @@
+//! Integer expressions accepted by the encode commands, e.g. `1 << 35`, `-(2**31)` or
+//! `0xdead_beef + 4`.
+//!
+//! Operators, from the loosest to the tightest binding:
+//!
+//! - `|`: bitwise or
+//! - `^`: bitwise xor
+//! - `&`: bitwise and
+//! - `<<`, `>>`: shifts
+//! - `+`, `-`: addition and subtraction
+//! - `*`, `/`, `%`: multiplication, division and remainder
+//! - `-`, `+`, `~`: negation, unary plus and bitwise not
+//! - `**`: exponentiation, right associative, so `-2**2` is `-4` and `2**3**2` is `2**9`
+//!
+//! Numbers are written as in `literal`, and `i8::MIN` to `u128::MAX` name the limits of the Rust
+//! integer types. Parentheses group as usual.
+//!
+//! Signed expressions are evaluated with checked `i128` arithmetic and unsigned ones with checked
+//! `u128` arithmetic. An intermediate result that doesn't fit is an overflow error pointing at the
+//! operation.
+
+use super::*;
+
+use std::ops::Range;
+
+/// Evaluates an expression for `es`.
+pub fn eval_signed(input: &str) -> Result<i128, Error> {
+    eval(input, &parse(input)?)
+}
+
+/// Evaluates an expression for `eu`.
+pub fn eval_unsigned(input: &str) -> Result<u128, Error> {
+    eval(input, &parse(input)?)
+}
+
+/// Where `input` stops being a plain number, e.g. the `<<` of `1 << 200` or the `i64::MIN` of
+/// `-i64::MIN`. `None` for plain numbers like `-123`, and for inputs that don't tokenize, which
+/// are left to the number parser to report.
+pub fn non_literal_span(input: &str) -> Option<Range<usize>> {
+    let tokens = tokenize(input).ok()?;
+    // A sign before the rest is fine, `-i64::MIN` points at the constant.
+    let is_sign = |i: usize| i == 0 && matches!(tokens[0].kind, TokenKind::Op("-" | "+"));
+    tokens
+        .iter()
+        .enumerate()
+        .find(|(i, token)| token.kind != TokenKind::Number && !is_sign(*i))
+        .map(|(_, token)| token.span.clone())
+}
+
+/// Operators made of more than one character come first, so they are tokenized as a whole.
+const OPERATORS: [&str; 14] = [
+    "**", "<<", ">>", "+", "-", "*", "/", "%", "&", "|", "^", "~", "(", ")",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TokenKind {
+    /// A number in any of the notations of `literal`, e.g. `0xff_ff`.
+    Number,
+    /// A constant like `i64::MIN`.
+    Name,
+    /// An operator or a parenthesis.
+    Op(&'static str),
+}
+
+#[derive(Debug, Clone)]
+struct Token {
+    kind: TokenKind,
+    span: Range<usize>,
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, Error> {
+    let mut tokens = vec![];
+    let mut pos = 0;
+
+    while let Some(c) = input[pos..].chars().next() {
+        let start = pos;
+        let kind = if c.is_whitespace() {
+            pos += c.len_utf8();
+            continue;
+        } else if c.is_ascii_alphanumeric() {
+            // Numbers and names are checked when parsed, so `0xfg` is reported as an invalid
+            // digit rather than as a number followed by a name.
+            pos += input[pos..]
+                .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_' || c == ':'))
+                .unwrap_or(input.len() - pos);
+            if c.is_ascii_digit() {
+                TokenKind::Number
+            } else {
+                TokenKind::Name
+            }
+        } else if let Some(op) = OPERATORS.iter().find(|op| input[pos..].starts_with(*op)) {
+            pos += op.len();
+            TokenKind::Op(op)
+        } else {
+            return Err(Error::Parse(format!("Unexpected character `{}`", c))
+                .at(input, pos..pos + c.len_utf8()));
+        };
+        tokens.push(Token {
+            kind,
+            span: start..pos,
+        });
+    }
+
+    Ok(tokens)
+}
+
+#[derive(Debug)]
+enum Expr {
+    /// A number or a constant, possibly negated. Kept as a sign and a magnitude so that
+    /// `-170141183460469231731687303715884105728` is `i128::MIN` rather than the negation of a
+    /// number that doesn't fit.
+    Literal {
+        negative: bool,
+        magnitude: u128,
+    },
+    Unary(&'static str, Box<Node>),
+    Binary(&'static str, Box<Node>, Box<Node>),
+}
+
+#[derive(Debug)]
+struct Node {
+    expr: Expr,
+    /// Location of the expression in the input, for errors.
+    span: Range<usize>,
+}
+
+/// Precedence of the binary operators, higher binds tighter.
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "|" => 1,
+        "^" => 2,
+        "&" => 3,
+        "<<" | ">>" => 4,
+        "+" | "-" => 5,
+        "*" | "/" | "%" => 6,
+        "**" => POW_PRECEDENCE,
+        _ => return None,
+    })
+}
+
+/// Binds tighter than the unary operators, whose operands are parsed at this precedence.
+const POW_PRECEDENCE: u8 = 8;
+
+struct Parser<'a> {
+    input: &'a str,
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+fn parse(input: &str) -> Result<Node, Error> {
+    let mut parser = Parser {
+        input,
+        tokens: tokenize(input)?,
+        pos: 0,
+    };
+    if parser.tokens.is_empty() {
+        return Err(Error::Parse("Expected a number".to_owned()));
+    }
+
+    let node = parser.expr(0)?;
+    match parser.tokens.get(parser.pos) {
+        Some(Token {
+            kind: TokenKind::Op(")"),
+            span,
+        }) => Err(Error::Parse("Unmatched `)`".to_owned()).at(input, span.clone())),
+        Some(token) => Err(Error::Parse(format!(
+            "Expected an operator, found `{}`",
+            &input[token.span.clone()]
+        ))
+        .at(input, token.span.clone())),
+        None => Ok(node),
+    }
+}
+
+impl Parser<'_> {
+    /// Parses an expression whose binary operators bind at least as tight as `min_precedence`.
+    fn expr(&mut self, min_precedence: u8) -> Result<Node, Error> {
+        let mut lhs = self.operand()?;
+
+        while let Some(Token {
+            kind: TokenKind::Op(op),
+            ..
+        }) = self.tokens.get(self.pos)
+        {
+            let op = *op;
+            let precedence = match precedence(op) {
+                Some(precedence) if precedence >= min_precedence => precedence,
+                _ => break,
+            };
+            self.pos += 1;
+
+            let rhs = if op == "**" {
+                self.expr(precedence)?
+            } else {
+                self.expr(precedence + 1)?
+            };
+            let span = lhs.span.start..rhs.span.end;
+            lhs = Node {
+                expr: Expr::Binary(op, Box::new(lhs), Box::new(rhs)),
+                span,
+            };
+        }
+
+        Ok(lhs)
+    }
+
+    /// Parses a number, a constant, a parenthesized expression or a unary operator applied to
+    /// one of those.
+    fn operand(&mut self) -> Result<Node, Error> {
+        let token = match self.tokens.get(self.pos) {
+            Some(token) => token.clone(),
+            None => {
+                let end = self.input.trim_end().len();
+                return Err(Error::Parse("Expected a number".to_owned()).at(self.input, end..end));
+            }
+        };
+        self.pos += 1;
+
+        match token.kind {
+            TokenKind::Number => Ok(Node {
+                expr: Expr::Literal {
+                    negative: false,
+                    magnitude: literal::parse_magnitude(self.input, token.span.clone())?,
+                },
+                span: token.span,
+            }),
+
+            TokenKind::Name => {
+                let name = &self.input[token.span.clone()];
+                let (negative, magnitude) = constant(name).ok_or_else(|| {
+                    Error::Parse(format!(
+                        "Unknown constant `{}`, constants are the limits of the integer types \
+                         like `i64::MIN` or `u32::MAX`",
+                        name
+                    ))
+                    .at(self.input, token.span.clone())
+                })?;
+                Ok(Node {
+                    expr: Expr::Literal {
+                        negative,
+                        magnitude,
+                    },
+                    span: token.span,
+                })
+            }
+
+            TokenKind::Op(op @ ("-" | "+" | "~")) => {
+                let operand = self.expr(POW_PRECEDENCE)?;
+                let span = token.span.start..operand.span.end;
+                let expr = match (op, operand.expr) {
+                    (
+                        "-",
+                        Expr::Literal {
+                            negative,
+                            magnitude,
+                        },
+                    ) => Expr::Literal {
+                        negative: !negative,
+                        magnitude,
+                    },
+                    ("+", expr) => expr,
+                    (op, expr) => Expr::Unary(
+                        op,
+                        Box::new(Node {
+                            expr,
+                            span: operand.span,
+                        }),
+                    ),
+                };
+                Ok(Node { expr, span })
+            }
+
+            TokenKind::Op("(") => {
+                let inner = self.expr(0)?;
+                match self.tokens.get(self.pos) {
+                    Some(Token {
+                        kind: TokenKind::Op(")"),
+                        span,
+                    }) => {
+                        let span = token.span.start..span.end;
+                        self.pos += 1;
+                        Ok(Node {
+                            expr: inner.expr,
+                            span,
+                        })
+                    }
+                    _ => Err(Error::Parse("Unclosed `(`".to_owned()).at(self.input, token.span)),
+                }
+            }
+
+            TokenKind::Op(op) => Err(Error::Parse(format!("Expected a number, found `{}`", op))
+                .at(self.input, token.span)),
+        }
+    }
+}
+
+/// Sign and magnitude of a constant like `i64::MIN`.
+fn constant(name: &str) -> Option<(bool, u128)> {
+    let (ty, limit) = name.split_once("::")?;
+    let bits: u32 = match ty.get(1..)? {
+        "8" => 8,
+        "16" => 16,
+        "32" => 32,
+        "64" => 64,
+        "128" => 128,
+        _ => return None,
+    };
+    match (&ty[..1], limit) {
+        ("i", "MIN") => Some((true, 1 << (bits - 1))),
+        ("i", "MAX") => Some((false, (1 << (bits - 1)) - 1)),
+        ("u", "MIN") => Some((false, 0)),
+        ("u", "MAX") => Some((false, u128::MAX >> (128 - bits))),
+        _ => None,
+    }
+}
+
+/// The checked arithmetic of `i128` and `u128`, for evaluating expressions with either.
+trait Int: Copy + PartialEq + fmt::Display {
+    /// "signed" or "unsigned", for errors.
+    const KIND: &'static str;
+    const ZERO: Self;
+    const MIN: Self;
+    const MAX: Self;
+
+    fn from_literal(negative: bool, magnitude: u128) -> Option<Self>;
+    fn is_negative(self) -> bool;
+    fn neg(self) -> Option<Self>;
+    fn not(self) -> Self;
+    fn add(self, rhs: Self) -> Option<Self>;
+    fn sub(self, rhs: Self) -> Option<Self>;
+    fn mul(self, rhs: Self) -> Option<Self>;
+    fn div(self, rhs: Self) -> Option<Self>;
+    fn rem(self, rhs: Self) -> Option<Self>;
+    fn pow(self, exp: u32) -> Option<Self>;
+    fn and(self, rhs: Self) -> Self;
+    fn or(self, rhs: Self) -> Self;
+    fn xor(self, rhs: Self) -> Self;
+    /// `self` as a shift amount or exponent, if it's not negative and fits in a `u32`.
+    fn to_u32(self) -> Option<u32>;
+
+    /// `self << amount`, or `None` if bits are shifted out. `amount` is less than 128.
+    fn shl(self, amount: u32) -> Option<Self>;
+    /// `self >> amount`, arithmetic for signed values. `amount` is less than 128.
+    fn shr(self, amount: u32) -> Self;
+}
+
+impl Int for i128 {
+    const KIND: &'static str = "signed";
+    const ZERO: Self = 0;
+    const MIN: Self = i128::MIN;
+    const MAX: Self = i128::MAX;
+
+    fn from_literal(negative: bool, magnitude: u128) -> Option<Self> {
+        if negative {
+            0i128.checked_sub_unsigned(magnitude)
+        } else {
+            i128::try_from(magnitude).ok()
+        }
+    }
+
+    fn is_negative(self) -> bool {
+        self < 0
+    }
+
+    fn neg(self) -> Option<Self> {
+        self.checked_neg()
+    }
+
+    fn not(self) -> Self {
+        !self
+    }
+
+    fn add(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs)
+    }
+
+    fn sub(self, rhs: Self) -> Option<Self> {
+        self.checked_sub(rhs)
+    }
+
+    fn mul(self, rhs: Self) -> Option<Self> {
+        self.checked_mul(rhs)
+    }
+
+    fn div(self, rhs: Self) -> Option<Self> {
+        self.checked_div(rhs)
+    }
+
+    fn rem(self, rhs: Self) -> Option<Self> {
+        self.checked_rem(rhs)
+    }
+
+    fn pow(self, exp: u32) -> Option<Self> {
+        self.checked_pow(exp)
+    }
+
+    fn and(self, rhs: Self) -> Self {
+        self & rhs
+    }
+
+    fn or(self, rhs: Self) -> Self {
+        self | rhs
+    }
+
+    fn xor(self, rhs: Self) -> Self {
+        self ^ rhs
+    }
+
+    fn to_u32(self) -> Option<u32> {
+        u32::try_from(self).ok()
+    }
+
+    fn shl(self, amount: u32) -> Option<Self> {
+        let shifted = self << amount;
+        (shifted >> amount == self).then_some(shifted)
+    }
+
+    fn shr(self, amount: u32) -> Self {
+        self >> amount
+    }
+}
+
+impl Int for u128 {
+    const KIND: &'static str = "unsigned";
+    const ZERO: Self = 0;
+    const MIN: Self = u128::MIN;
+    const MAX: Self = u128::MAX;
+
+    fn from_literal(negative: bool, magnitude: u128) -> Option<Self> {
+        if negative && magnitude != 0 {
+            None
+        } else {
+            Some(magnitude)
+        }
+    }
+
+    fn is_negative(self) -> bool {
+        false
+    }
+
+    fn neg(self) -> Option<Self> {
+        self.checked_neg()
+    }
+
+    fn not(self) -> Self {
+        !self
+    }
+
+    fn add(self, rhs: Self) -> Option<Self> {
+        self.checked_add(rhs)
+    }
+
+    fn sub(self, rhs: Self) -> Option<Self> {
+        self.checked_sub(rhs)
+    }
+
+    fn mul(self, rhs: Self) -> Option<Self> {
+        self.checked_mul(rhs)
+    }
+
+    fn div(self, rhs: Self) -> Option<Self> {
+        self.checked_div(rhs)
+    }
+
+    fn rem(self, rhs: Self) -> Option<Self> {
+        self.checked_rem(rhs)
+    }
+
+    fn pow(self, exp: u32) -> Option<Self> {
+        self.checked_pow(exp)
+    }
+
+    fn and(self, rhs: Self) -> Self {
+        self & rhs
+    }
+
+    fn or(self, rhs: Self) -> Self {
+        self | rhs
+    }
+
+    fn xor(self, rhs: Self) -> Self {
+        self ^ rhs
+    }
+
+    fn to_u32(self) -> Option<u32> {
+        u32::try_from(self).ok()
+    }
+
+    fn shl(self, amount: u32) -> Option<Self> {
+        let shifted = self << amount;
+        (shifted >> amount == self).then_some(shifted)
+    }
+
+    fn shr(self, amount: u32) -> Self {
+        self >> amount
+    }
+}
+
+fn eval<T: Int>(input: &str, node: &Node) -> Result<T, Error> {
+    let text = &input[node.span.clone()];
+    let overflow = || {
+        Error::Overflow(format!(
+            "`{}` doesn't fit in 128 bits, {} 128-bit values are {}..={}",
+            text,
+            T::KIND,
+            T::MIN,
+            T::MAX
+        ))
+        .at(input, node.span.clone())
+    };
+
+    match &node.expr {
+        Expr::Literal {
+            negative,
+            magnitude,
+        } => T::from_literal(*negative, *magnitude).ok_or_else(|| {
+            if *negative && T::MIN == T::ZERO {
+                Error::Parse("Unsigned numbers can't be negative".to_owned())
+                    .at(input, node.span.clone())
+            } else {
+                overflow()
+            }
+        }),
+
+        Expr::Unary(op, operand) => {
+            let value: T = eval(input, operand)?;
+            match *op {
+                "-" => value.neg().ok_or_else(overflow),
+                _ => Ok(value.not()),
+            }
+        }
+
+        Expr::Binary(op, lhs, rhs) => {
+            let a: T = eval(input, lhs)?;
+            let b: T = eval(input, rhs)?;
+            let rhs_error = |msg: String| Err(Error::Parse(msg).at(input, rhs.span.clone()));
+            match *op {
+                "+" => a.add(b).ok_or_else(overflow),
+                "-" => a.sub(b).ok_or_else(overflow),
+                "*" => a.mul(b).ok_or_else(overflow),
+                "/" | "%" if b == T::ZERO => rhs_error("Division by zero".to_owned()),
+                "/" => a.div(b).ok_or_else(overflow),
+                "%" => a.rem(b).ok_or_else(overflow),
+                "&" => Ok(a.and(b)),
+                "|" => Ok(a.or(b)),
+                "^" => Ok(a.xor(b)),
+                "<<" | ">>" => match b.to_u32().filter(|amount| *amount < 128) {
+                    Some(amount) if *op == "<<" => a.shl(amount).ok_or_else(overflow),
+                    Some(amount) => Ok(a.shr(amount)),
+                    None => rhs_error(format!("Shift amount {} is not in 0..128", b)),
+                },
+                _ => {
+                    if b.is_negative() {
+                        return rhs_error(format!("Negative exponent {}", b));
+                    }
+                    b.to_u32().and_then(|exp| a.pow(exp)).ok_or_else(overflow)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::message_and_span;
+
+    #[test]
+    fn test_non_literal_span() {
+        for s in [
+            "123", "-123", "+0x_ff", " 1_000 ", "12a4", "1 2", "", "-", "1 $",
+        ] {
+            assert_eq!(non_literal_span(s), None, "{}", s);
+        }
+        assert_eq!(non_literal_span("1 << 200"), Some(2..4));
+        assert_eq!(non_literal_span("-i64::MIN"), Some(1..9));
+        assert_eq!(non_literal_span("u128::MAX"), Some(0..9));
+        assert_eq!(non_literal_span("-(1)"), Some(1..2));
+        assert_eq!(non_literal_span("--1"), Some(1..2));
+    }
+
+    #[test]
+    fn test_eval() {
+        assert_eq!(eval_signed("1 << 35").unwrap(), 1 << 35);
+        assert_eq!(eval_signed("-(2**31)").unwrap(), -(1 << 31));
+        assert_eq!(eval_signed("0xdead_beef + 4").unwrap(), 0xdead_beef + 4);
+        assert_eq!(eval_unsigned("u32::MAX").unwrap(), u32::MAX as u128);
+        assert_eq!(eval_signed("i64::MIN").unwrap(), i64::MIN as i128);
+        assert_eq!(eval_signed("1 + 2 * 3").unwrap(), 7);
+        assert_eq!(eval_signed("(1 + 2) * 3").unwrap(), 9);
+        assert_eq!(eval_signed("10 - 3 - 2").unwrap(), 5);
+        assert_eq!(eval_signed("-7 / 2").unwrap(), -3);
+        assert_eq!(eval_signed("-7 % 2").unwrap(), -1);
+        assert_eq!(eval_signed("-2**2").unwrap(), -4);
+        assert_eq!(eval_signed("2**3**2").unwrap(), 512);
+        assert_eq!(eval_signed("2 ** -1 + 1").unwrap_err().exit_code(), 2);
+        assert_eq!(eval_signed("1 | 6 ^ 3 & 5").unwrap(), 1 | (6 ^ (3 & 5)));
+        assert_eq!(eval_signed("1 << 2 + 1").unwrap(), 8);
+        assert_eq!(eval_signed("~0").unwrap(), -1);
+        assert_eq!(eval_unsigned("~0").unwrap(), u128::MAX);
+        assert_eq!(eval_signed("-128 >> 2").unwrap(), -32);
+        assert_eq!(eval_signed("--5").unwrap(), 5);
+        assert_eq!(eval_signed("+5").unwrap(), 5);
+        assert_eq!(eval_signed("-0b11").unwrap(), -3);
+        assert_eq!(eval_unsigned("-0").unwrap(), 0);
+        assert_eq!(eval_unsigned(" 624485 ").unwrap(), 624485);
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(
+            eval_signed("-170141183460469231731687303715884105728").unwrap(),
+            i128::MIN
+        );
+        assert_eq!(
+            eval_signed("-0x8000_0000_0000_0000_0000_0000_0000_0000").unwrap(),
+            i128::MIN
+        );
+        assert_eq!(eval_signed("i128::MIN").unwrap(), i128::MIN);
+        assert_eq!(eval_signed("-i128::MAX - 1").unwrap(), i128::MIN);
+        assert_eq!(eval_unsigned("u128::MAX").unwrap(), u128::MAX);
+        assert_eq!(eval_signed("-1 << 127").unwrap(), i128::MIN);
+        assert_eq!(eval_unsigned("1 << 127").unwrap(), 1 << 127);
+
+        for (input, span) in [
+            ("i128::MAX + 1", 0..13),
+            ("-i128::MIN", 0..10),
+            ("i128::MIN / -1", 0..14),
+            ("1 << 127", 0..8),
+            ("2 ** 200", 0..8),
+            ("1 + u128::MAX", 4..13),
+            ("3 * (i64::MAX * i64::MAX * 4)", 4..29),
+        ] {
+            let err = eval_signed(input).unwrap_err();
+            assert_eq!(err.exit_code(), 4, "{}", input);
+            assert_eq!(err.location().unwrap().span, span, "{}", input);
+        }
+        assert_eq!(
+            message_and_span(eval_unsigned("1 - 2")).0,
+            "`1 - 2` doesn't fit in 128 bits, unsigned 128-bit values are 0..=\
+             340282366920938463463374607431768211455"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            message_and_span(eval_signed("1 / (2 - 2)")),
+            ("Division by zero".to_owned(), Some(4..11))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("1 << 128")),
+            ("Shift amount 128 is not in 0..128".to_owned(), Some(5..8))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("(1 + 2")),
+            ("Unclosed `(`".to_owned(), Some(0..1))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("1 + 2)")),
+            ("Unmatched `)`".to_owned(), Some(5..6))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("1 +")),
+            ("Expected a number".to_owned(), Some(3..3))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("1 2")),
+            ("Expected an operator, found `2`".to_owned(), Some(2..3))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("* 2")),
+            ("Expected a number, found `*`".to_owned(), Some(0..1))
+        );
+        assert_eq!(
+            message_and_span(eval_signed("1 $ 2")),
+            ("Unexpected character `$`".to_owned(), Some(2..3))
+        );
+        assert_eq!(message_and_span(eval_signed("i63::MAX")).1, Some(0..8));
+        assert_eq!(
+            message_and_span(eval_signed("1 + 0xfg")),
+            (
+                "Invalid digit `g` in hexadecimal number".to_owned(),
+                Some(7..8)
+            )
+        );
+        assert_eq!(
+            message_and_span(eval_unsigned("-5")),
+            ("Unsigned numbers can't be negative".to_owned(), Some(0..2))
+        );
+        assert_eq!(message_and_span(eval_signed("")).0, "Expected a number");
+    }
+}
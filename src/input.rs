@@ -0,0 +1,377 @@
+//! Parsing of the byte input of the decode commands.
+
+use super::*;
+
+use std::ops::Range;
+
+/// Bytes parsed from a string, with the location of each byte in the string.
+#[derive(Debug)]
+pub struct HexBytes {
+    pub bytes: Vec<u8>,
+
+    /// `spans[i]` is the byte range in the input that `bytes[i]` was parsed from.
+    pub spans: Vec<Range<usize>>,
+}
+
+/// Characters that separate bytes. Brackets and quotes are included so that array literals
+/// (`[0xe5, 0x8e]`, `{0xe5, 0x8e}`) and strings (`"\xe5\x8e"`) can be pasted as they are.
+fn is_separator(c: char) -> bool {
+    c.is_whitespace()
+        || matches!(
+            c,
+            ',' | ':' | ';' | '[' | ']' | '{' | '}' | '(' | ')' | '"' | '\'' | '&'
+        )
+}
+
+/// Parses hex bytes in any of the common notations:
+///
+/// - Separated by whitespace, commas or colons: `e5 8e 26`, `e5,8e,26`, `e5:8e:26`
+/// - Contiguous: `e58e26`
+/// - `0x` prefixed: `0xe5, 0x8e, 0x26`
+/// - `\x` escapes: `\xe5\x8e\x26`, `b"\xe5\x8e\x26"`
+/// - Array literals: `[0xE5, 0x8E, 0x26]`, `{0xe5, 0x8e, 0x26}`
+///
+/// Tokens that can't be split into bytes unambiguously, like `e58` or `0xe58e`, are rejected.
+pub fn parse_hex(s: &str) -> Result<HexBytes, Error> {
+    let mut hex = HexBytes {
+        bytes: vec![],
+        spans: vec![],
+    };
+
+    parse_hex_range(s, 0..s.len(), &mut hex)?;
+
+    if hex.bytes.is_empty() {
+        return Err(Error::Parse("No bytes to decode".to_owned()));
+    }
+
+    Ok(hex)
+}
+
+/// Parses the bytes in `range` of `s`, in any of the notations accepted by `parse_hex`.
+fn parse_hex_range(s: &str, range: Range<usize>, hex: &mut HexBytes) -> Result<(), Error> {
+    let mut token_start = None;
+    let mut chars = s[range.clone()]
+        .char_indices()
+        .map(|(i, c)| (range.start + i, c))
+        .peekable();
+    while let Some((i, c)) = chars.next() {
+        // `b` of a Rust byte string literal, `b"..."`.
+        let byte_string_prefix =
+            c == 'b' && token_start.is_none() && matches!(chars.peek(), Some((_, '"')));
+
+        if is_separator(c) || byte_string_prefix {
+            if let Some(start) = token_start.take() {
+                parse_token(s, start..i, hex)?;
+            }
+        } else if token_start.is_none() {
+            token_start = Some(i);
+        }
+    }
+    if let Some(start) = token_start {
+        parse_token(s, start..range.end, hex)?;
+    }
+    Ok(())
+}
+
+/// Parses the output of a hex dump tool: `xxd`, `hexdump -C`, `od -tx1`, or the memory dumps of
+/// `gdb` (`x/16xb`) and `lldb` (`memory read`). The offset column and the ASCII column are
+/// skipped, the rest of each line is parsed like `parse_hex` input.
+pub fn parse_dump(s: &str) -> Result<HexBytes, Error> {
+    let mut hex = HexBytes {
+        bytes: vec![],
+        spans: vec![],
+    };
+
+    // Whether lines start with an offset. Decided on the first line, as a line of `xxd -p`
+    // output looks like an offset on its own.
+    let mut has_offsets = None;
+
+    let mut line_start = 0;
+    for line in s.split_inclusive('\n') {
+        let range = line_start..line_start + line.trim_end().len();
+        line_start += line.len();
+
+        let line = &s[range.clone()];
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        if line.trim() == "*" {
+            return Err(Error::Parse(
+                "The dump has collapsed repeated lines (`*`), dump with `-v` to show all lines"
+                    .to_owned(),
+            )
+            .at(s, range));
+        }
+
+        let offset_end = dump_offset_end(line);
+        let has_offsets = *has_offsets
+            .get_or_insert_with(|| offset_end.is_some_and(|end| !line[end..].trim().is_empty()));
+
+        let mut data = range.clone();
+        if has_offsets {
+            match offset_end {
+                Some(end) => data.start += end,
+                None => {
+                    return Err(Error::Parse(
+                        "Expected an offset at the start of the line".to_owned(),
+                    )
+                    .at(
+                        s,
+                        range.start..range.start + line.len() - line.trim_start().len() + 1,
+                    ))
+                }
+            }
+        }
+        data.end = data.start + dump_data_len(&s[data.clone()]);
+
+        parse_hex_range(s, data, &mut hex)?;
+    }
+
+    if hex.bytes.is_empty() {
+        return Err(Error::Parse("No bytes to decode".to_owned()));
+    }
+
+    Ok(hex)
+}
+
+/// If the line starts with an offset, returns the index right after it.
+///
+/// Offsets are terminated with a colon (`00000000:`, `0x7ffe0010 <buf+16>:`) or are a long
+/// number (`00000000`, `0000020`).
+fn dump_offset_end(line: &str) -> Option<usize> {
+    let leading_ws = line.len() - line.trim_start().len();
+    let trimmed = line.trim_start();
+    let first_token_len = trimmed.find(char::is_whitespace).unwrap_or(trimmed.len());
+    let first_token = &trimmed[..first_token_len];
+
+    let is_number = |s: &str| {
+        let digits = s.strip_prefix("0x").unwrap_or(s);
+        !digits.is_empty() && digits.chars().all(|c| c.is_ascii_hexdigit())
+    };
+
+    if let Some(colon) = trimmed.find(':') {
+        let label = &trimmed[..colon];
+        let address = label.split_whitespace().next().unwrap_or("");
+        if is_number(address) && (address.starts_with("0x") || address.len() >= 6) {
+            return Some(leading_ws + colon + 1);
+        }
+    }
+
+    if first_token.len() >= 6 && is_number(first_token) {
+        return Some(leading_ws + first_token_len);
+    }
+
+    None
+}
+
+/// Length of the hex data part of a line after the offset, excluding the ASCII column.
+fn dump_data_len(data: &str) -> usize {
+    // `hexdump -C` puts the ASCII column in `|...|`, `od -tx1z` in `>...<`.
+    if data.ends_with('|') {
+        if let Some(i) = data.find("  |") {
+            return i;
+        }
+    }
+    if data.ends_with('<') {
+        if let Some(i) = data.find("  >") {
+            return i;
+        }
+    }
+
+    // `xxd` and `lldb` separate the ASCII column with two or more spaces. Skip leading spaces
+    // first as there may be more than one space after the offset.
+    let leading_ws = data.len() - data.trim_start().len();
+    match data[leading_ws..].find("  ") {
+        Some(i) => leading_ws + i,
+        None => data.len(),
+    }
+}
+
+/// Parses the token at `span` of `s`, which may contain any number of bytes.
+fn parse_token(s: &str, span: Range<usize>, hex: &mut HexBytes) -> Result<(), Error> {
+    let token = &s[span.clone()];
+
+    if token.starts_with("\\x") || token.starts_with("\\X") {
+        // One byte per escape: `\xe5\x8e`.
+        let mut pos = span.start;
+        while pos < span.end {
+            let rest = &s[pos..span.end];
+            let n_digits = rest[1..]
+                .find('\\')
+                .unwrap_or(rest.len() - 1)
+                .saturating_sub(1);
+            let escape_span = pos..pos + 2 + n_digits;
+            if !(rest.starts_with("\\x") || rest.starts_with("\\X"))
+                || n_digits == 0
+                || n_digits > 2
+            {
+                let escape_span = pos..escape_span.end.min(span.end);
+                return Err(Error::Parse(format!(
+                    "Expected a \\x escape with one or two hex digits, found {:?}",
+                    &s[escape_span.clone()]
+                ))
+                .at(s, escape_span));
+            }
+            push_digits(s, pos + 2..escape_span.end, escape_span.clone(), hex)?;
+            pos = escape_span.end;
+        }
+        return Ok(());
+    }
+
+    if let Some(digits) = token
+        .strip_prefix("0x")
+        .or_else(|| token.strip_prefix("0X"))
+    {
+        if digits.len() > 2 {
+            return Err(Error::Parse(format!(
+                "Ambiguous token {:?}: a 0x-prefixed token can only hold one byte, \
+                 separate the bytes with spaces or commas",
+                token
+            ))
+            .at(s, span));
+        }
+        return push_digits(s, span.start + 2..span.end, span, hex);
+    }
+
+    if token.len() <= 2 {
+        return push_digits(s, span.clone(), span, hex);
+    }
+
+    // Contiguous bytes: `e58e26`.
+    if !token.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::Parse(format!("Invalid hex bytes: {:?}", token)).at(s, span));
+    }
+    if !token.len().is_multiple_of(2) {
+        return Err(Error::Parse(format!(
+            "Ambiguous token {:?}: odd number of hex digits, can't split it into bytes",
+            token
+        ))
+        .at(s, span));
+    }
+    for start in (span.start..span.end).step_by(2) {
+        push_digits(s, start..start + 2, start..start + 2, hex)?;
+    }
+    Ok(())
+}
+
+/// Parses one byte from the hex digits at `digits` of `s`. `span` is the range reported in
+/// diagnostics, which includes prefixes like `0x`.
+fn push_digits(
+    s: &str,
+    digits: Range<usize>,
+    span: Range<usize>,
+    hex: &mut HexBytes,
+) -> Result<(), Error> {
+    let digits_str = &s[digits];
+    let byte = if digits_str.is_empty() || !digits_str.chars().all(|c| c.is_ascii_hexdigit()) {
+        None
+    } else {
+        u8::from_str_radix(digits_str, 16).ok()
+    };
+
+    match byte {
+        Some(byte) => {
+            hex.bytes.push(byte);
+            hex.spans.push(span);
+            Ok(())
+        }
+        None => Err(Error::Parse(format!("Invalid hex byte: {:?}", &s[span.clone()])).at(s, span)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(s: &str) -> Vec<u8> {
+        parse_hex(s).expect("Should parse bytes").bytes
+    }
+
+    #[test]
+    fn test_parse_hex_notations() {
+        let expected = vec![0xe5, 0x8e, 0x26];
+        assert_eq!(bytes("e5 8e 26"), expected);
+        assert_eq!(bytes("  e5  8e\t26\n"), expected);
+        assert_eq!(bytes("e58e26"), expected);
+        assert_eq!(bytes("e5:8e:26"), expected);
+        assert_eq!(bytes("0xe5,0x8e,0x26"), expected);
+        assert_eq!(bytes("0xe5, 0x8e, 0x26"), expected);
+        assert_eq!(bytes("[0xE5, 0x8E, 0x26]"), expected);
+        assert_eq!(bytes("{0xe5, 0x8e, 0x26}"), expected);
+        assert_eq!(bytes("\\xe5\\x8e\\x26"), expected);
+        assert_eq!(bytes("b\"\\xe5\\x8e\\x26\""), expected);
+        assert_eq!(bytes("e58e 26"), expected);
+        assert_eq!(bytes("7"), vec![7]);
+    }
+
+    #[test]
+    fn test_parse_hex_spans() {
+        let hex = parse_hex(" 0xe5, 8e26").expect("Should parse bytes");
+        assert_eq!(hex.spans, vec![1..5, 7..9, 9..11]);
+
+        let hex = parse_hex("\\xe5\\x8e").expect("Should parse bytes");
+        assert_eq!(hex.spans, vec![0..4, 4..8]);
+    }
+
+    #[test]
+    fn test_parse_dump() {
+        let expected = vec![0xe5, 0x8e, 0x26, 0x41, 0x42, 0x43, 0x44, 0x0a];
+
+        let xxd = "00000000: e58e 2641 4243 440a                 ..&ABCD.\n";
+        assert_eq!(parse_dump(xxd).expect("Should parse xxd").bytes, expected);
+
+        let hexdump = "00000000  e5 8e 26 41 42 43 44 0a                           |..&ABCD.|\n\
+                       00000008\n";
+        assert_eq!(
+            parse_dump(hexdump).expect("Should parse hexdump").bytes,
+            expected
+        );
+
+        let od = "0000000 e5 8e 26 41 42 43 44 0a\n0000010\n";
+        assert_eq!(parse_dump(od).expect("Should parse od").bytes, expected);
+
+        let gdb = "0x7fffffffe3a0:\t0xe5\t0x8e\t0x26\t0x41\n\
+                   0x7fffffffe3a4 <buf+4>:\t0x42\t0x43\t0x44\t0x0a\n";
+        assert_eq!(parse_dump(gdb).expect("Should parse gdb").bytes, expected);
+
+        let lldb = "0x100003f50: e5 8e 26 41 42 43 44 0a  ..&ABCD.\n";
+        assert_eq!(parse_dump(lldb).expect("Should parse lldb").bytes, expected);
+
+        let xxd_plain = "e58e264142434\n40a\n";
+        assert!(parse_dump(xxd_plain).is_err());
+        let xxd_plain = "e58e2641424344\n0a\n";
+        assert_eq!(
+            parse_dump(xxd_plain).expect("Should parse xxd -p").bytes,
+            expected
+        );
+    }
+
+    #[test]
+    fn test_parse_dump_collapsed_lines() {
+        let hexdump =
+            "00000000  00 00 00 00 00 00 00 00  00 00 00 00 00 00 00 00  |................|\n\
+                       *\n\
+                       00000020\n";
+        assert!(parse_dump(hexdump).is_err());
+    }
+
+    #[test]
+    fn test_parse_hex_errors() {
+        for s in [
+            "",
+            " , ",
+            "e58",
+            "0xe58e",
+            "zz",
+            "0x",
+            "\\x",
+            "\\xe5\\x",
+            "\\xe5\\x123",
+            "\\xe5x",
+            "e5é8",
+        ] {
+            assert!(parse_hex(s).is_err(), "{:?} should be rejected", s);
+        }
+    }
+}
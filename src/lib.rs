@@ -0,0 +1,1911 @@
+//! Reading and writing LEB128-encoded integers: the codec of the `leb128` command line tool.
+//!
+//! Values of up to 128 bits are read and written with the functions in `read` and `write`, which
+//! work on any `io::Read` or `io::Write`. The `_bits` variants check values against a narrower
+//! width following the WebAssembly rules, the `_strict` readers reject encodings longer than
+//! necessary, and the `_big` variants handle values of any size as `big::BigUint` and
+//! `big::BigInt`. The `ReadLeb128` and `WriteLeb128` extension traits provide the same as
+//! methods, e.g. `r.read_uleb128()` or `r.read_sleb_i64()`. `decode_uleb` and `decode_sleb`
+//! decode straight from a slice, without `io`, and `encode_uleb` and `encode_sleb` encode into a
+//! stack buffer. `encode_uleb_into` and `encode_sleb_into` write into a slice only when the whole
+//! encoding fits.
+//!
+//! ```
+//! use leb128_repl::{read, write};
+//!
+//! let mut buf = vec![];
+//! write::unsigned(&mut buf, 624485).unwrap();
+//! assert_eq!(buf, [0xe5, 0x8e, 0x26]);
+//!
+//! let mut readable = &buf[..];
+//! assert_eq!(read::unsigned(&mut readable).unwrap(), 624485);
+//!
+//! let mut readable = &[0xc0, 0xbb, 0x78][..];
+//! assert_eq!(read::signed_bits(&mut readable, 32).unwrap(), -123456);
+//! ```
+
+pub mod big;
+
+use big::{BigInt, BigUint};
+
+use std::fmt;
+use std::io;
+
+/// Set on every byte of an encoding except the last.
+pub const CONTINUATION_BIT: u8 = 1 << 7;
+
+/// The highest payload bit of a byte. In the last byte of a signed encoding it is the sign bit
+/// of the value.
+pub const SIGN_BIT: u8 = 1 << 6;
+
+/// The 7 payload bits of an encoded byte, without the continuation bit.
+pub fn low_bits_of_byte(byte: u8) -> u8 {
+    byte & !CONTINUATION_BIT
+}
+
+/// The low 7 bits of `val`, the payload of the next byte of its encoding.
+pub(crate) fn low_bits_of_u128(val: u128) -> u8 {
+    let byte = val & (u8::MAX as u128);
+    low_bits_of_byte(byte as u8)
+}
+
+/// Length of the longest LEB128 encoding of a `bits`-bit value.
+pub const fn max_encoded_len(bits: u32) -> usize {
+    bits.div_ceil(7) as usize
+}
+
+/// Length of the longest LEB128 encoding of a 128-bit value, the size of the buffers of
+/// `encode_uleb` and `encode_sleb`.
+pub const MAX_ENCODED_LEN: usize = max_encoded_len(128);
+
+/// Length of the encoding of `val` as unsigned LEB128, without encoding it.
+pub fn encoded_len_unsigned(val: u128) -> usize {
+    max_encoded_len(128 - val.leading_zeros()).max(1)
+}
+
+/// Length of the encoding of `val` as signed LEB128, without encoding it.
+pub fn encoded_len_signed(val: i128) -> usize {
+    // The bits of the magnitude, plus a sign bit. `val ^ (val >> 127)` is `!val` for negative
+    // values, whose highest clear bit is the highest bit that isn't a sign bit.
+    let magnitude_bits = 128 - (val ^ (val >> 127)).leading_zeros();
+    max_encoded_len(magnitude_bits + 1)
+}
+
+/// Whether `byte` can be the next byte of an unsigned LEB128 value with `remaining` bits of the
+/// value left. When at most 7 bits are left this is the last byte, so it can't set the
+/// continuation bit or any bit beyond the remaining ones. With no bits left there is no next
+/// byte, so no byte is valid.
+pub(crate) fn valid_unsigned_byte(byte: u8, remaining: u32) -> bool {
+    remaining > 7 || (remaining > 0 && byte >> remaining == 0)
+}
+
+/// Signed counterpart of `valid_unsigned_byte`: the bits of the last byte beyond the remaining
+/// ones must be copies of the sign bit of the value.
+pub(crate) fn valid_signed_byte(byte: u8, remaining: u32) -> bool {
+    if remaining > 7 {
+        return true;
+    }
+    if remaining == 0 {
+        return false;
+    }
+    let high_bits = byte >> (remaining - 1);
+    high_bits == 0 || high_bits == 0x7f >> (remaining - 1)
+}
+
+/// Why `unsigned_from` or `signed_from` stopped before the end of a value.
+enum Stop<E> {
+    /// Getting the next byte failed, e.g. at the end of the input.
+    Source(E),
+
+    /// Byte `index` of the value, `byte`, doesn't fit in the width being decoded.
+    Overflow { index: usize, byte: u8 },
+}
+
+/// Decodes an unsigned LEB128 value of at most `bits` bits from the bytes `next_byte` returns.
+/// The loop behind both `read::unsigned_bits` and `decode_uleb`, so they can't disagree on which
+/// encodings overflow.
+fn unsigned_from<E>(
+    bits: u32,
+    mut next_byte: impl FnMut() -> Result<u8, E>,
+) -> Result<u128, Stop<E>> {
+    let mut result = 0;
+    let mut shift = 0;
+    let mut index = 0;
+
+    loop {
+        let byte = next_byte().map_err(Stop::Source)?;
+        if !valid_unsigned_byte(byte, bits - shift) {
+            return Err(Stop::Overflow { index, byte });
+        }
+
+        result |= (low_bits_of_byte(byte) as u128) << shift;
+
+        if byte & CONTINUATION_BIT == 0 {
+            return Ok(result);
+        }
+
+        shift += 7;
+        index += 1;
+    }
+}
+
+/// Signed counterpart of `unsigned_from`, behind `read::signed_bits` and `decode_sleb`.
+fn signed_from<E>(
+    bits: u32,
+    mut next_byte: impl FnMut() -> Result<u8, E>,
+) -> Result<i128, Stop<E>> {
+    let mut result = 0;
+    let mut shift = 0;
+    let mut index = 0;
+
+    loop {
+        let byte = next_byte().map_err(Stop::Source)?;
+        if !valid_signed_byte(byte, bits - shift) {
+            return Err(Stop::Overflow { index, byte });
+        }
+
+        result |= (low_bits_of_byte(byte) as i128) << shift;
+        shift += 7;
+
+        if byte & CONTINUATION_BIT == 0 {
+            if shift < 128 && (SIGN_BIT & byte) == SIGN_BIT {
+                // Sign extend the result.
+                result |= !0 << shift;
+            }
+            return Ok(result);
+        }
+
+        index += 1;
+    }
+}
+
+/// A module for reading LEB128-encoded signed and unsigned integers.
+pub mod read {
+    use super::*;
+
+    use std::fmt;
+
+    /// An error type for reading LEB128-encoded values.
+    #[derive(Debug)]
+    #[allow(clippy::enum_variant_names)]
+    pub enum Error {
+        /// There was an underlying IO error.
+        IoError(io::Error),
+
+        /// The number being read is larger than can be represented.
+        Overflow,
+
+        /// With strict decoding, the number is encoded with more bytes than necessary.
+        NonCanonical {
+            /// Number of bytes beyond the minimal encoding.
+            redundant: usize,
+        },
+    }
+
+    impl From<io::Error> for Error {
+        fn from(e: io::Error) -> Self {
+            Error::IoError(e)
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+            match *self {
+                Error::IoError(ref e) => e.fmt(f),
+                Error::Overflow => {
+                    write!(f, "The number being read is larger than can be represented")
+                }
+                Error::NonCanonical { redundant } => write!(
+                    f,
+                    "The number being read is encoded with {} redundant bytes",
+                    redundant
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match *self {
+                Error::IoError(ref e) => Some(e),
+                Error::Overflow | Error::NonCanonical { .. } => None,
+            }
+        }
+    }
+
+    /// Reads an unsigned LEB128 value of at most 128 bits.
+    pub fn unsigned<R>(r: &mut R) -> Result<u128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        unsigned_bits(r, 128)
+    }
+
+    /// Reads an unsigned LEB128 value of at most `bits` bits, e.g. 32 for a `u32`. As in the
+    /// WebAssembly spec, encodings longer than `ceil(bits / 7)` bytes and encodings that set
+    /// bits beyond `bits` in their last byte are rejected with `Error::Overflow`.
+    ///
+    /// Panics if `bits` is not between 1 and 128.
+    pub fn unsigned_bits<R>(r: &mut R, bits: u32) -> Result<u128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        assert!((1..=128).contains(&bits), "Invalid bit width {}", bits);
+
+        let mut next_byte = || read_byte(r);
+        match unsigned_from(bits, &mut next_byte) {
+            Ok(result) => Ok(result),
+            Err(Stop::Source(e)) => Err(e.into()),
+            Err(Stop::Overflow { byte, .. }) => skip_rest(byte, next_byte),
+        }
+    }
+
+    /// Reads a signed LEB128 value of at most 128 bits.
+    pub fn signed<R>(r: &mut R) -> Result<i128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        signed_bits(r, 128)
+    }
+
+    /// Reads a signed LEB128 value of at most `bits` bits, e.g. 64 for an `i64`. As in the
+    /// WebAssembly spec, encodings longer than `ceil(bits / 7)` bytes and encodings whose last
+    /// byte doesn't sign extend the value to `bits` bits are rejected with `Error::Overflow`.
+    ///
+    /// Panics if `bits` is not between 1 and 128.
+    pub fn signed_bits<R>(r: &mut R, bits: u32) -> Result<i128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        assert!((1..=128).contains(&bits), "Invalid bit width {}", bits);
+
+        let mut next_byte = || read_byte(r);
+        match signed_from(bits, &mut next_byte) {
+            Ok(result) => Ok(result),
+            Err(Stop::Source(e)) => Err(e.into()),
+            Err(Stop::Overflow { byte, .. }) => skip_rest(byte, next_byte),
+        }
+    }
+
+    fn read_byte<R>(r: &mut R) -> io::Result<u8>
+    where
+        R: ?Sized + io::Read,
+    {
+        let mut buf = [0];
+        r.read_exact(&mut buf)?;
+        Ok(buf[0])
+    }
+
+    /// After an overflowing `byte`, reads the rest of the value so the reader is left at the
+    /// next one, and fails with `Error::Overflow`.
+    fn skip_rest<T>(
+        mut byte: u8,
+        mut next_byte: impl FnMut() -> io::Result<u8>,
+    ) -> Result<T, Error> {
+        while byte & CONTINUATION_BIT != 0 {
+            byte = next_byte()?;
+        }
+        Err(Error::Overflow)
+    }
+
+    /// A reader that counts the bytes read from it.
+    struct Counting<'a, R: ?Sized> {
+        inner: &'a mut R,
+        count: usize,
+    }
+
+    impl<R: ?Sized + io::Read> io::Read for Counting<'_, R> {
+        fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+            let n = self.inner.read(buf)?;
+            self.count += n;
+            Ok(n)
+        }
+    }
+
+    /// Checks that a value read from `len` bytes has no shorter encoding of `minimal_len` bytes.
+    fn check_canonical(len: usize, minimal_len: usize) -> Result<(), Error> {
+        if len > minimal_len {
+            return Err(Error::NonCanonical {
+                redundant: len - minimal_len,
+            });
+        }
+        Ok(())
+    }
+
+    /// Like `unsigned`, but rejects encodings longer than necessary, e.g. `80 00` for 0, with
+    /// `Error::NonCanonical`. The whole value is read either way.
+    pub fn unsigned_strict<R>(r: &mut R) -> Result<u128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        unsigned_bits_strict(r, 128)
+    }
+
+    /// Like `unsigned_bits`, but rejects encodings longer than necessary with
+    /// `Error::NonCanonical`.
+    pub fn unsigned_bits_strict<R>(r: &mut R, bits: u32) -> Result<u128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        let mut counting = Counting { inner: r, count: 0 };
+        let val = unsigned_bits(&mut counting, bits)?;
+        check_canonical(counting.count, encoded_len_unsigned(val))?;
+        Ok(val)
+    }
+
+    /// Like `signed`, but rejects encodings longer than necessary, e.g. `ff 7f` for -1, with
+    /// `Error::NonCanonical`. The whole value is read either way.
+    pub fn signed_strict<R>(r: &mut R) -> Result<i128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        signed_bits_strict(r, 128)
+    }
+
+    /// Like `signed_bits`, but rejects encodings longer than necessary with
+    /// `Error::NonCanonical`.
+    pub fn signed_bits_strict<R>(r: &mut R, bits: u32) -> Result<i128, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        let mut counting = Counting { inner: r, count: 0 };
+        let val = signed_bits(&mut counting, bits)?;
+        check_canonical(counting.count, encoded_len_signed(val))?;
+        Ok(val)
+    }
+
+    /// Reads an unsigned LEB128 value of any size.
+    pub fn unsigned_big<R>(r: &mut R) -> Result<BigUint, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        let mut result = BigUint::default();
+        let mut shift = 0;
+
+        loop {
+            let mut buf = [0];
+            r.read_exact(&mut buf)?;
+
+            result.set_seven_bits_at(shift, low_bits_of_byte(buf[0]));
+
+            if buf[0] & CONTINUATION_BIT == 0 {
+                return Ok(result);
+            }
+
+            shift += 7;
+        }
+    }
+
+    /// Reads a signed LEB128 value of any size.
+    pub fn signed_big<R>(r: &mut R) -> Result<BigInt, Error>
+    where
+        R: ?Sized + io::Read,
+    {
+        let mut result = BigUint::default();
+        let mut shift = 0;
+        let mut byte;
+
+        loop {
+            let mut buf = [0];
+            r.read_exact(&mut buf)?;
+
+            byte = buf[0];
+            result.set_seven_bits_at(shift, low_bits_of_byte(byte));
+            shift += 7;
+
+            if byte & CONTINUATION_BIT == 0 {
+                break;
+            }
+        }
+
+        if (SIGN_BIT & byte) == SIGN_BIT {
+            // `result` is the two's complement of the value in `shift` bits, so the magnitude is
+            // `2^shift - result`.
+            let mut magnitude = result.complement(shift);
+            magnitude.add_one();
+            return Ok(BigInt::new(true, magnitude));
+        }
+
+        Ok(BigInt::from(result))
+    }
+}
+
+/// A module for writing LEB128-encoded signed and unsigned integers.
+pub mod write {
+    use super::*;
+
+    use std::fmt;
+
+    /// An error type for writing LEB128-encoded values.
+    #[derive(Debug)]
+    #[allow(clippy::enum_variant_names)]
+    pub enum Error {
+        /// There was an underlying IO error.
+        IoError(io::Error),
+
+        /// The number being written doesn't fit in the given number of bits.
+        Overflow,
+
+        /// The number being written needs more bytes than the length it is padded to.
+        PaddingTooShort {
+            /// Length of the minimal encoding of the number.
+            needed: usize,
+        },
+    }
+
+    impl From<io::Error> for Error {
+        fn from(e: io::Error) -> Self {
+            Error::IoError(e)
+        }
+    }
+
+    impl fmt::Display for Error {
+        fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+            match *self {
+                Error::IoError(ref e) => e.fmt(f),
+                Error::Overflow => {
+                    write!(f, "The number being written doesn't fit in the given bits")
+                }
+                Error::PaddingTooShort { needed } => write!(
+                    f,
+                    "The number being written needs {} bytes, more than the padded length",
+                    needed
+                ),
+            }
+        }
+    }
+
+    impl std::error::Error for Error {
+        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+            match *self {
+                Error::IoError(ref e) => Some(e),
+                Error::Overflow | Error::PaddingTooShort { .. } => None,
+            }
+        }
+    }
+
+    /// Writes `val` as unsigned LEB128 in as few bytes as possible. Returns the number of bytes
+    /// written.
+    ///
+    /// The encoding is passed to `w` with a single `write_all`, but a writer that runs out of
+    /// room keeps what fit: on a `&mut [u8]` that is too short the slice ends up holding the
+    /// start of the encoding and the error is `WriteZero`. Use `encode_uleb_into` to write into a
+    /// slice only when the whole encoding fits.
+    pub fn unsigned<W>(w: &mut W, val: u128) -> Result<usize, io::Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut buf = [0; MAX_ENCODED_LEN];
+        let encoded = encode_uleb(val, &mut buf);
+        w.write_all(encoded)?;
+        Ok(encoded.len())
+    }
+
+    /// Writes `val` as signed LEB128 in as few bytes as possible. Returns the number of bytes
+    /// written.
+    ///
+    /// The encoding is passed to `w` with a single `write_all`, but a writer that runs out of
+    /// room keeps what fit: on a `&mut [u8]` that is too short the slice ends up holding the
+    /// start of the encoding and the error is `WriteZero`. Use `encode_sleb_into` to write into a
+    /// slice only when the whole encoding fits.
+    pub fn signed<W>(w: &mut W, val: i128) -> Result<usize, io::Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut buf = [0; MAX_ENCODED_LEN];
+        let encoded = encode_sleb(val, &mut buf);
+        w.write_all(encoded)?;
+        Ok(encoded.len())
+    }
+
+    /// Writes an unsigned LEB128 value of any size.
+    pub fn unsigned_big<W>(w: &mut W, val: &BigUint) -> Result<usize, io::Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        let mut bytes_written = 0;
+        let mut shift = 0;
+        loop {
+            let mut byte = val.seven_bits_at(shift);
+            shift += 7;
+            let done = val.bits() <= shift;
+            if !done {
+                // More bytes to come, so set the continuation bit.
+                byte |= CONTINUATION_BIT;
+            }
+
+            let buf = [byte];
+            w.write_all(&buf)?;
+            bytes_written += 1;
+
+            if done {
+                return Ok(bytes_written);
+            }
+        }
+    }
+
+    /// Writes a signed LEB128 value of any size.
+    pub fn signed_big<W>(w: &mut W, val: &BigInt) -> Result<usize, io::Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        // The bits of a negative value in two's complement are the complement of the bits of
+        // its magnitude minus one, so encode those and flip them.
+        let mut bits = val.magnitude().clone();
+        let flip = if val.is_negative() {
+            bits.sub_one();
+            0x7f
+        } else {
+            0
+        };
+
+        let mut bytes_written = 0;
+        let mut shift = 0;
+        loop {
+            let mut byte = bits.seven_bits_at(shift) ^ flip;
+            // Done when the rest of the bits, including the sign bit of this byte, are all sign
+            // bits.
+            let done = bits.bits() <= shift + 6;
+            shift += 7;
+            if !done {
+                // More bytes to come, so set the continuation bit.
+                byte |= CONTINUATION_BIT;
+            }
+
+            let buf = [byte];
+            w.write_all(&buf)?;
+            bytes_written += 1;
+
+            if done {
+                return Ok(bytes_written);
+            }
+        }
+    }
+
+    /// Writes `val` as unsigned LEB128 padded to exactly `len` bytes, with continuation bits set
+    /// on redundant zero bytes. Wasm object files use 5 bytes for `u32` values, so relocations
+    /// can be patched in place.
+    pub fn unsigned_padded<W>(w: &mut W, val: u128, len: usize) -> Result<usize, Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        let needed = encoded_len_unsigned(val);
+        if needed > len {
+            return Err(Error::PaddingTooShort { needed });
+        }
+
+        for i in 0..len {
+            let mut byte = low_bits_of_u128(val.checked_shr(7 * i as u32).unwrap_or(0));
+            if i != len - 1 {
+                byte |= CONTINUATION_BIT;
+            }
+            w.write_all(&[byte])?;
+        }
+        Ok(len)
+    }
+
+    /// Writes `val` as signed LEB128 padded to exactly `len` bytes, with continuation bits set
+    /// on redundant sign extension bytes.
+    pub fn signed_padded<W>(w: &mut W, val: i128, len: usize) -> Result<usize, Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        let needed = encoded_len_signed(val);
+        if needed > len {
+            return Err(Error::PaddingTooShort { needed });
+        }
+
+        for i in 0..len {
+            let mut byte = low_bits_of_byte((val >> (7 * i).min(127)) as u8);
+            if i != len - 1 {
+                byte |= CONTINUATION_BIT;
+            }
+            w.write_all(&[byte])?;
+        }
+        Ok(len)
+    }
+
+    /// Writes `val` as unsigned LEB128 after checking that it fits in `bits` bits, e.g. 32 for
+    /// a `u32`.
+    ///
+    /// Panics if `bits` is not between 1 and 128.
+    pub fn unsigned_bits<W>(w: &mut W, val: u128, bits: u32) -> Result<usize, Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        assert!((1..=128).contains(&bits), "Invalid bit width {}", bits);
+
+        if bits < 128 && val >> bits != 0 {
+            return Err(Error::Overflow);
+        }
+        Ok(unsigned(w, val)?)
+    }
+
+    /// Writes `val` as signed LEB128 after checking that it fits in `bits` bits, e.g. 64 for an
+    /// `i64`.
+    ///
+    /// Panics if `bits` is not between 1 and 128.
+    pub fn signed_bits<W>(w: &mut W, val: i128, bits: u32) -> Result<usize, Error>
+    where
+        W: ?Sized + io::Write,
+    {
+        assert!((1..=128).contains(&bits), "Invalid bit width {}", bits);
+
+        let sign_bits = val >> (bits - 1);
+        if sign_bits != 0 && sign_bits != -1 {
+            return Err(Error::Overflow);
+        }
+        Ok(signed(w, val)?)
+    }
+}
+
+/// An error decoding a LEB128 value from a slice with `decode_uleb` or `decode_sleb`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DecodeError {
+    /// The slice ends in the middle of the value.
+    Truncated {
+        /// The length of the slice, where the next byte of the value would be.
+        offset: usize,
+    },
+
+    /// The value doesn't fit in 128 bits.
+    Overflow {
+        /// Index of the 19th byte of the value, the last one it can have, which carries bits
+        /// beyond 128 or sets the continuation bit.
+        offset: usize,
+    },
+}
+
+impl DecodeError {
+    /// Index in the slice of the byte the error is about.
+    pub fn offset(&self) -> usize {
+        match *self {
+            DecodeError::Truncated { offset } | DecodeError::Overflow { offset } => offset,
+        }
+    }
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match *self {
+            DecodeError::Truncated { offset } => write!(
+                f,
+                "The input ends at offset {} in the middle of a LEB128 value",
+                offset
+            ),
+            DecodeError::Overflow { offset } => write!(
+                f,
+                "The number being read is larger than can be represented, the byte at offset {} \
+                 carries bits beyond 128",
+                offset
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// Decodes an unsigned LEB128 value from the start of `bytes`, accepting the same encodings as
+/// `read::unsigned`. Returns the value and the number of bytes it takes.
+///
+/// ```
+/// use leb128_repl::{decode_uleb, DecodeError};
+///
+/// assert_eq!(decode_uleb(&[0xe5, 0x8e, 0x26, 0x01]), Ok((624485, 3)));
+/// assert_eq!(decode_uleb(&[0xe5, 0x8e]), Err(DecodeError::Truncated { offset: 2 }));
+/// ```
+pub fn decode_uleb(bytes: &[u8]) -> Result<(u128, usize), DecodeError> {
+    let mut rest = bytes.iter();
+    let result = unsigned_from(128, || rest.next().copied().ok_or(()));
+    decoded(bytes, rest.len(), result)
+}
+
+/// Decodes a signed LEB128 value from the start of `bytes`, accepting the same encodings as
+/// `read::signed`. Returns the value and the number of bytes it takes.
+///
+/// ```
+/// use leb128_repl::decode_sleb;
+///
+/// assert_eq!(decode_sleb(&[0xc0, 0xbb, 0x78]), Ok((-123456, 3)));
+/// ```
+pub fn decode_sleb(bytes: &[u8]) -> Result<(i128, usize), DecodeError> {
+    let mut rest = bytes.iter();
+    let result = signed_from(128, || rest.next().copied().ok_or(()));
+    decoded(bytes, rest.len(), result)
+}
+
+/// The result of `decode_uleb` or `decode_sleb`, given the number of bytes left after the value.
+fn decoded<T>(
+    bytes: &[u8],
+    rest: usize,
+    result: Result<T, Stop<()>>,
+) -> Result<(T, usize), DecodeError> {
+    match result {
+        Ok(value) => Ok((value, bytes.len() - rest)),
+        Err(Stop::Source(())) => Err(DecodeError::Truncated {
+            offset: bytes.len(),
+        }),
+        Err(Stop::Overflow { index, .. }) => Err(DecodeError::Overflow { offset: index }),
+    }
+}
+
+/// Encodes `val` as unsigned LEB128 into `buf`, without `io` or allocating. Returns the prefix of
+/// `buf` holding the encoding. `write::unsigned` writes what this returns.
+///
+/// ```
+/// use leb128_repl::{encode_uleb, MAX_ENCODED_LEN};
+///
+/// let mut buf = [0; MAX_ENCODED_LEN];
+/// assert_eq!(encode_uleb(624485, &mut buf), [0xe5, 0x8e, 0x26]);
+/// ```
+pub fn encode_uleb(mut val: u128, buf: &mut [u8; MAX_ENCODED_LEN]) -> &[u8] {
+    let mut len = 0;
+    loop {
+        let mut byte = low_bits_of_u128(val);
+        val >>= 7;
+        if val != 0 {
+            // More bytes to come, so set the continuation bit.
+            byte |= CONTINUATION_BIT;
+        }
+
+        buf[len] = byte;
+        len += 1;
+
+        if val == 0 {
+            return &buf[..len];
+        }
+    }
+}
+
+/// Encodes `val` as signed LEB128 into `buf`, without `io` or allocating. Returns the prefix of
+/// `buf` holding the encoding. `write::signed` writes what this returns.
+pub fn encode_sleb(mut val: i128, buf: &mut [u8; MAX_ENCODED_LEN]) -> &[u8] {
+    let mut len = 0;
+    loop {
+        let mut byte = val as u8;
+        // Keep the sign bit for testing.
+        val >>= 6;
+        let done = val == 0 || val == -1;
+        if done {
+            byte &= !CONTINUATION_BIT;
+        } else {
+            // Remove the sign bit.
+            val >>= 1;
+            // More bytes to come, so set the continuation bit.
+            byte |= CONTINUATION_BIT;
+        }
+
+        buf[len] = byte;
+        len += 1;
+
+        if done {
+            return &buf[..len];
+        }
+    }
+}
+
+/// The error of `encode_uleb_into` and `encode_sleb_into` when the encoding doesn't fit in the
+/// slice. Nothing is written to the slice.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BufferTooSmall {
+    /// Length of the encoding.
+    pub needed: usize,
+    /// Length of the slice.
+    pub available: usize,
+}
+
+impl fmt::Display for BufferTooSmall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        write!(
+            f,
+            "The number being written needs {} bytes, but only {} are available",
+            self.needed, self.available
+        )
+    }
+}
+
+impl std::error::Error for BufferTooSmall {}
+
+/// Encodes `val` as unsigned LEB128 at the start of `buf`, all or nothing: unlike
+/// `write::unsigned` with a slice writer, which leaves a truncated encoding behind when the slice
+/// is too short, nothing is written unless the whole encoding fits. Returns the number of bytes
+/// written.
+///
+/// ```
+/// use leb128_repl::{encode_uleb_into, BufferTooSmall};
+///
+/// let mut buf = [0; 2];
+/// let err = encode_uleb_into(624485, &mut buf).unwrap_err();
+/// assert_eq!(err, BufferTooSmall { needed: 3, available: 2 });
+/// assert_eq!(buf, [0, 0]);
+/// ```
+pub fn encode_uleb_into(val: u128, buf: &mut [u8]) -> Result<usize, BufferTooSmall> {
+    let mut encoded = [0; MAX_ENCODED_LEN];
+    copy_encoding(encode_uleb(val, &mut encoded), buf)
+}
+
+/// Encodes `val` as signed LEB128 at the start of `buf`, all or nothing, like
+/// `encode_uleb_into`. Returns the number of bytes written.
+pub fn encode_sleb_into(val: i128, buf: &mut [u8]) -> Result<usize, BufferTooSmall> {
+    let mut encoded = [0; MAX_ENCODED_LEN];
+    copy_encoding(encode_sleb(val, &mut encoded), buf)
+}
+
+/// Copies `encoded` to the start of `buf` if it fits.
+fn copy_encoding(encoded: &[u8], buf: &mut [u8]) -> Result<usize, BufferTooSmall> {
+    let available = buf.len();
+    match buf.get_mut(..encoded.len()) {
+        Some(prefix) => {
+            prefix.copy_from_slice(encoded);
+            Ok(encoded.len())
+        }
+        None => Err(BufferTooSmall {
+            needed: encoded.len(),
+            available,
+        }),
+    }
+}
+
+/// Extension methods for reading LEB128 values from any `io::Read`, including `dyn io::Read`.
+///
+/// ```
+/// use leb128_repl::ReadLeb128;
+///
+/// let mut readable = &[0xe5, 0x8e, 0x26, 0x7f][..];
+/// assert_eq!(readable.read_uleb_u32().unwrap(), 624485);
+/// assert_eq!(readable.read_sleb128().unwrap(), -1);
+/// ```
+pub trait ReadLeb128: io::Read {
+    /// Reads an unsigned LEB128 value of at most 128 bits, see `read::unsigned`.
+    fn read_uleb128(&mut self) -> Result<u128, read::Error> {
+        read::unsigned(self)
+    }
+
+    /// Reads a signed LEB128 value of at most 128 bits, see `read::signed`.
+    fn read_sleb128(&mut self) -> Result<i128, read::Error> {
+        read::signed(self)
+    }
+
+    /// Reads a `u8`, rejecting encodings of wider values as `read::unsigned_bits` does.
+    fn read_uleb_u8(&mut self) -> Result<u8, read::Error> {
+        read::unsigned_bits(self, 8).map(|val| val as u8)
+    }
+
+    /// Reads a `u16`, rejecting encodings of wider values as `read::unsigned_bits` does.
+    fn read_uleb_u16(&mut self) -> Result<u16, read::Error> {
+        read::unsigned_bits(self, 16).map(|val| val as u16)
+    }
+
+    /// Reads a `u32`, rejecting encodings of wider values as `read::unsigned_bits` does.
+    fn read_uleb_u32(&mut self) -> Result<u32, read::Error> {
+        read::unsigned_bits(self, 32).map(|val| val as u32)
+    }
+
+    /// Reads a `u64`, rejecting encodings of wider values as `read::unsigned_bits` does.
+    fn read_uleb_u64(&mut self) -> Result<u64, read::Error> {
+        read::unsigned_bits(self, 64).map(|val| val as u64)
+    }
+
+    /// Reads an `i8`, rejecting encodings of wider values as `read::signed_bits` does.
+    fn read_sleb_i8(&mut self) -> Result<i8, read::Error> {
+        read::signed_bits(self, 8).map(|val| val as i8)
+    }
+
+    /// Reads an `i16`, rejecting encodings of wider values as `read::signed_bits` does.
+    fn read_sleb_i16(&mut self) -> Result<i16, read::Error> {
+        read::signed_bits(self, 16).map(|val| val as i16)
+    }
+
+    /// Reads an `i32`, rejecting encodings of wider values as `read::signed_bits` does.
+    fn read_sleb_i32(&mut self) -> Result<i32, read::Error> {
+        read::signed_bits(self, 32).map(|val| val as i32)
+    }
+
+    /// Reads an `i64`, rejecting encodings of wider values as `read::signed_bits` does.
+    fn read_sleb_i64(&mut self) -> Result<i64, read::Error> {
+        read::signed_bits(self, 64).map(|val| val as i64)
+    }
+}
+
+impl<R: ?Sized + io::Read> ReadLeb128 for R {}
+
+/// Extension methods for writing LEB128 values to any `io::Write`, including `dyn io::Write`.
+///
+/// ```
+/// use leb128_repl::WriteLeb128;
+///
+/// let mut buf = vec![];
+/// buf.write_uleb128(624485).unwrap();
+/// buf.write_sleb_i64(i64::MIN).unwrap();
+/// assert_eq!(buf.len(), 3 + 10);
+/// ```
+pub trait WriteLeb128: io::Write {
+    /// Writes `val` as unsigned LEB128, see `write::unsigned`. Returns the number of bytes
+    /// written.
+    fn write_uleb128(&mut self, val: u128) -> Result<usize, io::Error> {
+        write::unsigned(self, val)
+    }
+
+    /// Writes `val` as signed LEB128, see `write::signed`. Returns the number of bytes written.
+    fn write_sleb128(&mut self, val: i128) -> Result<usize, io::Error> {
+        write::signed(self, val)
+    }
+
+    /// Writes a `u8` with `write::unsigned_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_uleb_u8(&mut self, val: u8) -> Result<usize, write::Error> {
+        write::unsigned_bits(self, val.into(), 8)
+    }
+
+    /// Writes a `u16` with `write::unsigned_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_uleb_u16(&mut self, val: u16) -> Result<usize, write::Error> {
+        write::unsigned_bits(self, val.into(), 16)
+    }
+
+    /// Writes a `u32` with `write::unsigned_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_uleb_u32(&mut self, val: u32) -> Result<usize, write::Error> {
+        write::unsigned_bits(self, val.into(), 32)
+    }
+
+    /// Writes a `u64` with `write::unsigned_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_uleb_u64(&mut self, val: u64) -> Result<usize, write::Error> {
+        write::unsigned_bits(self, val.into(), 64)
+    }
+
+    /// Writes an `i8` with `write::signed_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_sleb_i8(&mut self, val: i8) -> Result<usize, write::Error> {
+        write::signed_bits(self, val.into(), 8)
+    }
+
+    /// Writes an `i16` with `write::signed_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_sleb_i16(&mut self, val: i16) -> Result<usize, write::Error> {
+        write::signed_bits(self, val.into(), 16)
+    }
+
+    /// Writes an `i32` with `write::signed_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_sleb_i32(&mut self, val: i32) -> Result<usize, write::Error> {
+        write::signed_bits(self, val.into(), 32)
+    }
+
+    /// Writes an `i64` with `write::signed_bits`, whose width check it always passes. Returns the
+    /// number of bytes written.
+    fn write_sleb_i64(&mut self, val: i64) -> Result<usize, write::Error> {
+        write::signed_bits(self, val.into(), 64)
+    }
+}
+
+impl<W: ?Sized + io::Write> WriteLeb128 for W {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_low_bits_of_byte() {
+        for i in 0..127 {
+            assert_eq!(i, low_bits_of_byte(i));
+            assert_eq!(i, low_bits_of_byte(i | CONTINUATION_BIT));
+        }
+    }
+
+    #[test]
+    fn test_low_bits_of_u128() {
+        for i in 0u128..127 {
+            assert_eq!(i as u8, low_bits_of_u128(1 << 16 | i));
+            assert_eq!(
+                i as u8,
+                low_bits_of_u128(i << 16 | i | (CONTINUATION_BIT as u128))
+            );
+        }
+    }
+
+    #[test]
+    fn test_valid_byte() {
+        assert!(valid_unsigned_byte(0xff, 8));
+        assert!(valid_unsigned_byte(0x7f, 7));
+        assert!(!valid_unsigned_byte(0x80, 7));
+        assert!(!valid_unsigned_byte(0x02, 1));
+        assert!(!valid_unsigned_byte(0x00, 0));
+
+        assert!(valid_signed_byte(0x3f, 7));
+        assert!(valid_signed_byte(0x7f, 1));
+        assert!(!valid_signed_byte(0x3e, 1));
+        assert!(!valid_signed_byte(0x00, 0));
+        assert!(!valid_signed_byte(0x7f, 0));
+    }
+
+    #[test]
+    fn test_read_unsigned() {
+        let buf = [2u8];
+        let mut readable = &buf[..];
+        assert_eq!(
+            2,
+            read::unsigned(&mut readable).expect("Should read number")
+        );
+
+        let buf = [127u8];
+        let mut readable = &buf[..];
+        assert_eq!(
+            127,
+            read::unsigned(&mut readable).expect("Should read number")
+        );
+
+        let buf = [CONTINUATION_BIT, 1];
+        let mut readable = &buf[..];
+        assert_eq!(
+            128,
+            read::unsigned(&mut readable).expect("Should read number")
+        );
+
+        let buf = [1u8 | CONTINUATION_BIT, 1];
+        let mut readable = &buf[..];
+        assert_eq!(
+            129,
+            read::unsigned(&mut readable).expect("Should read number")
+        );
+
+        let buf = [2u8 | CONTINUATION_BIT, 1];
+        let mut readable = &buf[..];
+        assert_eq!(
+            130,
+            read::unsigned(&mut readable).expect("Should read number")
+        );
+
+        let buf = [57u8 | CONTINUATION_BIT, 100];
+        let mut readable = &buf[..];
+        assert_eq!(
+            12857,
+            read::unsigned(&mut readable).expect("Should read number")
+        );
+    }
+
+    #[test]
+    fn test_read_unsigned_thru_dyn_trait() {
+        fn read(r: &mut dyn io::Read) -> u128 {
+            read::unsigned(r).expect("Should read number")
+        }
+
+        let buf = [0u8];
+
+        let mut readable = &buf[..];
+        assert_eq!(0, read(&mut readable));
+
+        let mut readable = io::Cursor::new(buf);
+        assert_eq!(0, read(&mut readable));
+    }
+
+    // Examples from the DWARF 4 standard, section 7.6, figure 23.
+    #[test]
+    fn test_read_signed() {
+        let buf = [2u8];
+        let mut readable = &buf[..];
+        assert_eq!(2, read::signed(&mut readable).expect("Should read number"));
+
+        let buf = [0x7eu8];
+        let mut readable = &buf[..];
+        assert_eq!(-2, read::signed(&mut readable).expect("Should read number"));
+
+        let buf = [127u8 | CONTINUATION_BIT, 0];
+        let mut readable = &buf[..];
+        assert_eq!(
+            127,
+            read::signed(&mut readable).expect("Should read number")
+        );
+
+        let buf = [1u8 | CONTINUATION_BIT, 0x7f];
+        let mut readable = &buf[..];
+        assert_eq!(
+            -127,
+            read::signed(&mut readable).expect("Should read number")
+        );
+
+        let buf = [CONTINUATION_BIT, 1];
+        let mut readable = &buf[..];
+        assert_eq!(
+            128,
+            read::signed(&mut readable).expect("Should read number")
+        );
+
+        let buf = [CONTINUATION_BIT, 0x7f];
+        let mut readable = &buf[..];
+        assert_eq!(
+            -128,
+            read::signed(&mut readable).expect("Should read number")
+        );
+
+        let buf = [1u8 | CONTINUATION_BIT, 1];
+        let mut readable = &buf[..];
+        assert_eq!(
+            129,
+            read::signed(&mut readable).expect("Should read number")
+        );
+
+        let buf = [0x7fu8 | CONTINUATION_BIT, 0x7e];
+        let mut readable = &buf[..];
+        assert_eq!(
+            -129,
+            read::signed(&mut readable).expect("Should read number")
+        );
+    }
+
+    #[test]
+    fn test_read_signed_thru_dyn_trait() {
+        fn read(r: &mut dyn io::Read) -> i128 {
+            read::signed(r).expect("Should read number")
+        }
+
+        let buf = [0u8];
+
+        let mut readable = &buf[..];
+        assert_eq!(0, read(&mut readable));
+
+        let mut readable = io::Cursor::new(buf);
+        assert_eq!(0, read(&mut readable));
+    }
+
+    #[test]
+    fn test_read_signed_127_bits() {
+        let buf = [
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            CONTINUATION_BIT,
+            0x40,
+        ];
+        let mut readable = &buf[..];
+        assert_eq!(
+            -0x400000000000000000000000000000,
+            read::signed(&mut readable).expect("Should read number")
+        );
+    }
+
+    #[test]
+    fn test_read_unsigned_not_enough_data() {
+        let buf = [CONTINUATION_BIT];
+        let mut readable = &buf[..];
+        match read::unsigned(&mut readable) {
+            Err(read::Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            otherwise => panic!("Unexpected: {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn test_read_signed_not_enough_data() {
+        let buf = [CONTINUATION_BIT];
+        let mut readable = &buf[..];
+        match read::signed(&mut readable) {
+            Err(read::Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
+            otherwise => panic!("Unexpected: {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn test_write_unsigned_not_enough_space() {
+        let mut buf = [0; 1];
+        let mut writable = &mut buf[..];
+        match write::unsigned(&mut writable, 128) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
+            otherwise => panic!("Unexpected: {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn test_write_signed_not_enough_space() {
+        let mut buf = [0; 1];
+        let mut writable = &mut buf[..];
+        match write::signed(&mut writable, 128) {
+            Err(e) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
+            otherwise => panic!("Unexpected: {:?}", otherwise),
+        }
+    }
+
+    #[test]
+    fn test_encode_into_not_enough_space() {
+        let mut buf = [0xaa; 2];
+        assert_eq!(
+            encode_uleb_into(1 << 14, &mut buf),
+            Err(BufferTooSmall {
+                needed: 3,
+                available: 2
+            })
+        );
+        assert_eq!(
+            encode_sleb_into(-8193, &mut buf),
+            Err(BufferTooSmall {
+                needed: 3,
+                available: 2
+            })
+        );
+        // Untouched, where `write::unsigned` leaves the first two bytes behind.
+        assert_eq!(buf, [0xaa; 2]);
+        let mut partial = [0xaa; 2];
+        assert!(write::unsigned(&mut &mut partial[..], 1 << 14).is_err());
+        assert_eq!(partial, [0x80, 0x80]);
+        assert!(write::signed(&mut &mut partial[..], -8193).is_err());
+        assert_eq!(partial, [0xff, 0xbf]);
+
+        assert_eq!(encode_uleb_into(128, &mut buf), Ok(2));
+        assert_eq!(buf, [0x80, 0x01]);
+        assert_eq!(encode_sleb_into(-1, &mut buf), Ok(1));
+        assert_eq!(buf, [0x7f, 0x01]);
+
+        let mut buf = [0; MAX_ENCODED_LEN + 1];
+        assert_eq!(encode_uleb_into(u128::MAX, &mut buf), Ok(MAX_ENCODED_LEN));
+        assert_eq!(
+            encode_sleb_into(i128::MIN, &mut []),
+            Err(BufferTooSmall {
+                needed: MAX_ENCODED_LEN,
+                available: 0
+            })
+        );
+    }
+
+    #[test]
+    fn test_write_unsigned_thru_dyn_trait() {
+        fn write(w: &mut dyn io::Write, val: u128) -> usize {
+            write::unsigned(w, val).expect("Should write number")
+        }
+        let mut buf = [0u8; 1];
+
+        let mut writable = &mut buf[..];
+        assert_eq!(write(&mut writable, 0), 1);
+        assert_eq!(buf[0], 0);
+
+        let mut writable = Vec::from(&buf[..]);
+        assert_eq!(write(&mut writable, 0), 1);
+        assert_eq!(buf[0], 0);
+    }
+
+    #[test]
+    fn test_write_signed_thru_dyn_trait() {
+        fn write(w: &mut dyn io::Write, val: i128) -> usize {
+            write::signed(w, val).expect("Should write number")
+        }
+        let mut buf = [0u8; 1];
+
+        let mut writable = &mut buf[..];
+        assert_eq!(write(&mut writable, 0), 1);
+        assert_eq!(buf[0], 0);
+
+        let mut writable = Vec::from(&buf[..]);
+        assert_eq!(write(&mut writable, 0), 1);
+        assert_eq!(buf[0], 0);
+    }
+
+    #[test]
+    fn dogfood_signed() {
+        fn inner(i: i128) {
+            let mut buf = [0u8; 1024];
+
+            let mut writable = &mut buf[..];
+            write::signed(&mut writable, i).expect("Should write signed number");
+
+            let mut readable = &buf[..];
+            let result = read::signed(&mut readable).expect("Should be able to read it back again");
+            assert_eq!(i, result);
+        }
+        for i in -513..513 {
+            inner(i);
+        }
+        inner(i64::MIN as i128);
+    }
+
+    #[test]
+    fn dogfood_unsigned() {
+        for i in 0..1025 {
+            let mut buf = [0u8; 1024];
+
+            {
+                let mut writable = &mut buf[..];
+                write::unsigned(&mut writable, i).expect("Should write signed number");
+            }
+
+            let mut readable = &buf[..];
+            let result =
+                read::unsigned(&mut readable).expect("Should be able to read it back again");
+            assert_eq!(i, result);
+        }
+    }
+
+    #[test]
+    fn test_read_unsigned_overflow() {
+        let buf = [
+            2u8 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            1,
+        ];
+        let mut readable = &buf[..];
+        assert!(read::unsigned(&mut readable).is_err());
+    }
+
+    #[test]
+    fn test_read_signed_overflow() {
+        let buf = [
+            2u8 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            2 | CONTINUATION_BIT,
+            1,
+        ];
+        let mut readable = &buf[..];
+        assert!(read::signed(&mut readable).is_err());
+    }
+
+    #[test]
+    fn test_read_128_bit_limits() {
+        let mut buf = vec![];
+        write::unsigned(&mut buf, u128::MAX).expect("Should write number");
+        assert_eq!(buf.len(), 19);
+        assert_eq!(buf[18], 0x03);
+        assert_eq!(read::unsigned(&mut &buf[..]).unwrap(), u128::MAX);
+
+        for i in [i128::MIN, i128::MAX] {
+            let mut buf = vec![];
+            write::signed(&mut buf, i).expect("Should write number");
+            assert_eq!(buf.len(), 19);
+            assert_eq!(read::signed(&mut &buf[..]).unwrap(), i);
+        }
+    }
+
+    // Examples from the WebAssembly spec test suite (binary-leb128.wast).
+    #[test]
+    fn test_read_bits() {
+        // Unused bits of the last byte are zero.
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x0f];
+        assert_eq!(read::unsigned_bits(&mut &buf[..], 32).unwrap(), 0xf000_0000);
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x10];
+        assert!(read::unsigned_bits(&mut &buf[..], 32).is_err());
+        let buf = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert_eq!(
+            read::unsigned_bits(&mut &buf[..], 32).unwrap(),
+            u32::MAX as u128
+        );
+        // Longer than ceil(32 / 7) = 5 bytes, even though the value fits.
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x00];
+        assert!(read::unsigned_bits(&mut &buf[..], 32).is_err());
+        assert_eq!(read::unsigned_bits(&mut &buf[..], 64).unwrap(), 0);
+        let buf = [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01];
+        assert_eq!(
+            read::unsigned_bits(&mut &buf[..], 64).unwrap(),
+            u64::MAX as u128
+        );
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x02];
+        assert!(read::unsigned_bits(&mut &buf[..], 64).is_err());
+
+        // Unused bits of the last byte are copies of the sign bit.
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x78];
+        assert_eq!(
+            read::signed_bits(&mut &buf[..], 32).unwrap(),
+            i32::MIN as i128
+        );
+        let buf = [0xff, 0xff, 0xff, 0xff, 0x07];
+        assert_eq!(
+            read::signed_bits(&mut &buf[..], 32).unwrap(),
+            i32::MAX as i128
+        );
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x70];
+        assert!(read::signed_bits(&mut &buf[..], 32).is_err());
+        let buf = [0xff, 0xff, 0xff, 0xff, 0x0f];
+        assert!(read::signed_bits(&mut &buf[..], 32).is_err());
+        let buf = [0xff, 0xff, 0xff, 0xff, 0x7f];
+        assert_eq!(read::signed_bits(&mut &buf[..], 32).unwrap(), -1);
+        let buf = [0xff, 0xff, 0xff, 0xff, 0xff, 0x7f];
+        assert!(read::signed_bits(&mut &buf[..], 32).is_err());
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f];
+        assert_eq!(
+            read::signed_bits(&mut &buf[..], 64).unwrap(),
+            i64::MIN as i128
+        );
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x01];
+        assert!(read::signed_bits(&mut &buf[..], 64).is_err());
+
+        assert_eq!(read::unsigned_bits(&mut &[0x01][..], 1).unwrap(), 1);
+        assert!(read::unsigned_bits(&mut &[0x02][..], 1).is_err());
+        assert_eq!(read::signed_bits(&mut &[0x7f][..], 1).unwrap(), -1);
+        assert!(read::signed_bits(&mut &[0x01][..], 1).is_err());
+
+        // The rest of the value is skipped after an error.
+        let buf = [0x80, 0x80, 0x80, 0x80, 0x80, 0x00, 0x05];
+        let mut readable = &buf[..];
+        assert!(read::unsigned_bits(&mut readable, 32).is_err());
+        assert_eq!(read::unsigned_bits(&mut readable, 32).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_write_bits() {
+        let mut buf = vec![];
+        assert_eq!(write::unsigned_bits(&mut buf, 255, 8).unwrap(), 2);
+        assert!(matches!(
+            write::unsigned_bits(&mut buf, 256, 8),
+            Err(write::Error::Overflow)
+        ));
+        assert_eq!(write::signed_bits(&mut buf, -128, 8).unwrap(), 2);
+        assert_eq!(write::signed_bits(&mut buf, 127, 8).unwrap(), 2);
+        assert!(matches!(
+            write::signed_bits(&mut buf, 128, 8),
+            Err(write::Error::Overflow)
+        ));
+        assert!(matches!(
+            write::signed_bits(&mut buf, -129, 8),
+            Err(write::Error::Overflow)
+        ));
+        assert!(write::signed_bits(&mut buf, i128::MIN, 128).is_ok());
+        assert!(write::unsigned_bits(&mut buf, u128::MAX, 128).is_ok());
+    }
+
+    #[test]
+    fn test_big() {
+        // Same bytes as the 128-bit functions.
+        for i in [0, 1, 63, 64, 127, 128, 624485, u64::MAX as u128, u128::MAX] {
+            let mut buf = vec![];
+            write::unsigned(&mut buf, i).unwrap();
+            let mut big_buf = vec![];
+            write::unsigned_big(&mut big_buf, &BigUint::from(i)).unwrap();
+            assert_eq!(big_buf, buf, "{}", i);
+            assert_eq!(read::unsigned_big(&mut &buf[..]).unwrap(), BigUint::from(i));
+        }
+        for i in [
+            i128::MIN,
+            -129,
+            -128,
+            -65,
+            -64,
+            -1,
+            0,
+            63,
+            64,
+            127,
+            128,
+            -123456,
+            i128::MAX,
+        ] {
+            let mut buf = vec![];
+            write::signed(&mut buf, i).unwrap();
+            let mut big_buf = vec![];
+            write::signed_big(&mut big_buf, &BigInt::from(i)).unwrap();
+            assert_eq!(big_buf, buf, "{}", i);
+            assert_eq!(read::signed_big(&mut &buf[..]).unwrap(), BigInt::from(i));
+        }
+
+        // Beyond 128 bits.
+        let big: BigUint = ("1".to_owned() + &"0".repeat(60)).parse().unwrap();
+        let mut buf = vec![];
+        assert_eq!(write::unsigned_big(&mut buf, &big).unwrap(), 29);
+        assert_eq!(read::unsigned_big(&mut &buf[..]).unwrap(), big);
+
+        let big = BigInt::new(true, big);
+        let mut buf = vec![];
+        write::signed_big(&mut buf, &big).unwrap();
+        assert_eq!(read::signed_big(&mut &buf[..]).unwrap(), big);
+
+        let buf = [0x80; 19]
+            .iter()
+            .chain(&[0x7e])
+            .copied()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            read::signed_big(&mut &buf[..]).unwrap().to_string(),
+            "-21778071482940061661655974875633165533184"
+        );
+
+        let buf = [CONTINUATION_BIT];
+        assert!(read::unsigned_big(&mut &buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_write_padded() {
+        let mut buf = vec![];
+        write::unsigned_padded(&mut buf, 0, 5).unwrap();
+        assert_eq!(buf, [0x80, 0x80, 0x80, 0x80, 0x00]);
+
+        let mut buf = vec![];
+        write::unsigned_padded(&mut buf, 624485, 5).unwrap();
+        assert_eq!(buf, [0xe5, 0x8e, 0xa6, 0x80, 0x00]);
+
+        let mut buf = vec![];
+        write::unsigned_padded(&mut buf, 624485, 3).unwrap();
+        assert_eq!(buf, [0xe5, 0x8e, 0x26]);
+
+        let mut buf = vec![];
+        write::unsigned_padded(&mut buf, u64::MAX as u128, 10).unwrap();
+        assert_eq!(
+            buf,
+            [0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x01]
+        );
+
+        let mut buf = vec![];
+        assert_eq!(write::unsigned_padded(&mut buf, u128::MAX, 20).unwrap(), 20);
+        assert!(matches!(
+            write::unsigned_padded(&mut buf, 624485, 2),
+            Err(write::Error::PaddingTooShort { needed: 3 })
+        ));
+
+        let mut buf = vec![];
+        write::signed_padded(&mut buf, -1, 5).unwrap();
+        assert_eq!(buf, [0xff, 0xff, 0xff, 0xff, 0x7f]);
+
+        let mut buf = vec![];
+        write::signed_padded(&mut buf, 1, 3).unwrap();
+        assert_eq!(buf, [0x81, 0x80, 0x00]);
+
+        let mut buf = vec![];
+        write::signed_padded(&mut buf, -123456, 5).unwrap();
+        assert_eq!(buf, [0xc0, 0xbb, 0xf8, 0xff, 0x7f]);
+
+        let mut buf = vec![];
+        assert_eq!(write::signed_padded(&mut buf, i128::MIN, 20).unwrap(), 20);
+        assert!(matches!(
+            write::signed_padded(&mut buf, 64, 1),
+            Err(write::Error::PaddingTooShort { needed: 2 })
+        ));
+
+        // Padded encodings read back as the same value.
+        for len in 3..=10 {
+            let mut buf = vec![];
+            write::signed_padded(&mut buf, -123456, len).unwrap();
+            assert_eq!(read::signed(&mut &buf[..]).unwrap(), -123456);
+            let mut buf = vec![];
+            write::unsigned_padded(&mut buf, 624485, len).unwrap();
+            assert_eq!(read::unsigned(&mut &buf[..]).unwrap(), 624485);
+        }
+    }
+
+    #[test]
+    fn test_read_strict() {
+        assert_eq!(read::unsigned_strict(&mut &[0x00][..]).unwrap(), 0);
+        let buf = [0xe5, 0x8e, 0x26];
+        assert_eq!(read::unsigned_strict(&mut &buf[..]).unwrap(), 624485);
+        let buf = [0x80, 0x80, 0x00];
+        assert!(matches!(
+            read::unsigned_strict(&mut &buf[..]),
+            Err(read::Error::NonCanonical { redundant: 2 })
+        ));
+        let buf = [0xe5, 0x8e, 0xa6, 0x80, 0x00];
+        assert!(matches!(
+            read::unsigned_strict(&mut &buf[..]),
+            Err(read::Error::NonCanonical { redundant: 2 })
+        ));
+
+        assert_eq!(read::signed_strict(&mut &[0x7f][..]).unwrap(), -1);
+        assert_eq!(read::signed_strict(&mut &[0xc0, 0x00][..]).unwrap(), 64);
+        assert_eq!(read::signed_strict(&mut &[0xbf, 0x7f][..]).unwrap(), -65);
+        assert!(matches!(
+            read::signed_strict(&mut &[0xff, 0x7f][..]),
+            Err(read::Error::NonCanonical { redundant: 1 })
+        ));
+        assert!(matches!(
+            read::signed_strict(&mut &[0x81, 0x00][..]),
+            Err(read::Error::NonCanonical { redundant: 1 })
+        ));
+
+        // Still reads the whole value.
+        let buf = [0x80, 0x00, 0x05];
+        let mut readable = &buf[..];
+        assert!(read::unsigned_bits_strict(&mut readable, 32).is_err());
+        assert_eq!(read::unsigned_bits_strict(&mut readable, 32).unwrap(), 5);
+    }
+
+    #[test]
+    fn test_extension_traits() {
+        let mut buf = vec![];
+        buf.write_uleb128(624485).unwrap();
+        buf.write_sleb128(-123456).unwrap();
+        buf.write_uleb128(u64::MAX.into()).unwrap();
+        buf.write_sleb128(i8::MIN.into()).unwrap();
+
+        let mut readable = &buf[..];
+        assert_eq!(readable.read_uleb128().unwrap(), 624485);
+        assert_eq!(readable.read_sleb128().unwrap(), -123456);
+        assert_eq!(readable.read_uleb_u64().unwrap(), u64::MAX);
+        assert_eq!(readable.read_sleb_i8().unwrap(), i8::MIN);
+        assert!(readable.is_empty());
+
+        // Width checks follow `read::unsigned_bits` and `read::signed_bits`.
+        let mut readable = &[0x80, 0x02, 0x80, 0x7f][..];
+        assert!(matches!(
+            readable.read_uleb_u8(),
+            Err(read::Error::Overflow)
+        ));
+        assert_eq!(readable.read_sleb_i8().unwrap(), -128);
+        assert_eq!((&[0xff, 0x01][..]).read_uleb_u16().unwrap(), 255);
+        assert_eq!((&[0xff, 0x7f][..]).read_sleb_i16().unwrap(), -1);
+        assert_eq!((&[0x7f][..]).read_uleb_u32().unwrap(), 127);
+        assert_eq!((&[0x40][..]).read_sleb_i32().unwrap(), -64);
+        assert_eq!(
+            (&[0x80, 0x80, 0x80, 0x80, 0x78][..])
+                .read_sleb_i32()
+                .unwrap(),
+            i32::MIN
+        );
+        assert_eq!(
+            (&[0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x80, 0x7f][..])
+                .read_sleb_i64()
+                .unwrap(),
+            i64::MIN
+        );
+
+        // The width-specific writers write the same bytes as `write::unsigned` and
+        // `write::signed`, and the readers of the same width read them back.
+        let mut buf = vec![];
+        assert_eq!(buf.write_uleb_u8(u8::MAX).unwrap(), 2);
+        assert_eq!(buf.write_uleb_u16(u16::MAX).unwrap(), 3);
+        assert_eq!(buf.write_uleb_u32(624485).unwrap(), 3);
+        assert_eq!(buf.write_uleb_u64(u64::MAX).unwrap(), 10);
+        assert_eq!(buf.write_sleb_i8(i8::MIN).unwrap(), 2);
+        assert_eq!(buf.write_sleb_i16(-1).unwrap(), 1);
+        assert_eq!(buf.write_sleb_i32(i32::MIN).unwrap(), 5);
+        assert_eq!(buf.write_sleb_i64(i64::MIN).unwrap(), 10);
+        assert_eq!(buf[..5], [0xff, 0x01, 0xff, 0xff, 0x03]);
+
+        let mut readable = &buf[..];
+        assert_eq!(readable.read_uleb_u8().unwrap(), u8::MAX);
+        assert_eq!(readable.read_uleb_u16().unwrap(), u16::MAX);
+        assert_eq!(readable.read_uleb_u32().unwrap(), 624485);
+        assert_eq!(readable.read_uleb_u64().unwrap(), u64::MAX);
+        assert_eq!(readable.read_sleb_i8().unwrap(), i8::MIN);
+        assert_eq!(readable.read_sleb_i16().unwrap(), -1);
+        assert_eq!(readable.read_sleb_i32().unwrap(), i32::MIN);
+        assert_eq!(readable.read_sleb_i64().unwrap(), i64::MIN);
+        assert!(readable.is_empty());
+
+        let mut full = [0u8; 1];
+        assert!(matches!(
+            (&mut full[..]).write_uleb_u16(300),
+            Err(write::Error::IoError(_))
+        ));
+    }
+
+    #[test]
+    fn test_extension_traits_thru_dyn_trait() {
+        fn read(r: &mut dyn io::Read) -> (u128, i32) {
+            let unsigned = r.read_uleb128().expect("Should read number");
+            let signed = r.read_sleb_i32().expect("Should read number");
+            (unsigned, signed)
+        }
+
+        fn write(w: &mut dyn io::Write) -> usize {
+            w.write_uleb128(128).expect("Should write number")
+                + w.write_sleb128(-1).expect("Should write number")
+        }
+
+        let mut buf = vec![];
+        assert_eq!(write(&mut buf), 3);
+        assert_eq!(buf, [0x80, 0x01, 0x7f]);
+
+        let mut writer: Box<dyn io::Write> = Box::new(vec![]);
+        assert_eq!(writer.write_uleb_u32(128).unwrap(), 2);
+        assert_eq!(writer.write_sleb_i64(-2).unwrap(), 1);
+
+        let mut readable = &buf[..];
+        assert_eq!(read(&mut readable), (128, -1));
+
+        let mut readable = io::Cursor::new(buf);
+        assert_eq!(read(&mut readable), (128, -1));
+
+        let mut boxed: Box<dyn io::Read> = Box::new(&[0x05, 0x7e][..]);
+        assert_eq!(boxed.read_uleb_u32().unwrap(), 5);
+        assert_eq!(boxed.read_sleb_i64().unwrap(), -2);
+    }
+
+    #[test]
+    fn test_decode_slice() {
+        // Same values and lengths as the `io::Read` functions.
+        for i in [
+            0,
+            1,
+            127,
+            128,
+            624485,
+            u64::MAX as u128,
+            1 << 126,
+            u128::MAX,
+        ] {
+            let mut buf = vec![];
+            write::unsigned(&mut buf, i).unwrap();
+            assert_eq!(decode_uleb(&buf), Ok((i, buf.len())), "{}", i);
+            buf.push(0xff);
+            assert_eq!(decode_uleb(&buf), Ok((i, buf.len() - 1)), "{}", i);
+        }
+        for i in [
+            i128::MIN,
+            -123456,
+            -65,
+            -64,
+            -1,
+            0,
+            63,
+            64,
+            624485,
+            i128::MAX,
+        ] {
+            let mut buf = vec![];
+            write::signed(&mut buf, i).unwrap();
+            assert_eq!(decode_sleb(&buf), Ok((i, buf.len())), "{}", i);
+        }
+
+        assert_eq!(decode_uleb(&[]), Err(DecodeError::Truncated { offset: 0 }));
+        assert_eq!(
+            decode_sleb(&[0x80, 0x80]),
+            Err(DecodeError::Truncated { offset: 2 })
+        );
+
+        let mut buf = [0xff; 20];
+        buf[19] = 0x00;
+        assert_eq!(decode_uleb(&buf), Err(DecodeError::Overflow { offset: 18 }));
+        assert_eq!(decode_sleb(&buf), Err(DecodeError::Overflow { offset: 18 }));
+        buf[18] = 0x04;
+        assert_eq!(decode_uleb(&buf), Err(DecodeError::Overflow { offset: 18 }));
+        assert_eq!(decode_uleb(&buf).unwrap_err().offset(), 18);
+        buf[18] = 0x03;
+        assert_eq!(decode_uleb(&buf), Ok((u128::MAX, 19)));
+
+        // The overflow checks of `read::signed`.
+        let buf = [0xff; 18]
+            .iter()
+            .chain(&[0x3e])
+            .copied()
+            .collect::<Vec<u8>>();
+        assert_eq!(decode_sleb(&buf), Err(DecodeError::Overflow { offset: 18 }));
+        assert!(read::signed(&mut &buf[..]).is_err());
+    }
+
+    #[test]
+    fn test_encode_stack_buffer() {
+        let mut values: Vec<u128> = vec![0, u128::MAX];
+        for shift in 0..128 {
+            values.extend([(1 << shift) - 1, 1 << shift, (1 << shift) + 1]);
+        }
+
+        for &i in &values {
+            let mut expected = vec![];
+            write::unsigned(&mut expected, i).unwrap();
+            let mut buf = [0; MAX_ENCODED_LEN];
+            assert_eq!(encode_uleb(i, &mut buf), expected, "{}", i);
+            assert_eq!(encoded_len_unsigned(i), expected.len(), "{}", i);
+
+            for i in [i as i128, !(i as i128)] {
+                let mut expected = vec![];
+                write::signed(&mut expected, i).unwrap();
+                assert_eq!(encode_sleb(i, &mut buf), expected, "{}", i);
+                assert_eq!(encoded_len_signed(i), expected.len(), "{}", i);
+            }
+        }
+
+        assert_eq!(encoded_len_unsigned(0), 1);
+        assert_eq!(encoded_len_unsigned(u128::MAX), MAX_ENCODED_LEN);
+        assert_eq!(encoded_len_signed(63), 1);
+        assert_eq!(encoded_len_signed(64), 2);
+        assert_eq!(encoded_len_signed(-64), 1);
+        assert_eq!(encoded_len_signed(-65), 2);
+        assert_eq!(encoded_len_signed(i128::MIN), MAX_ENCODED_LEN);
+    }
+
+    #[test]
+    fn test_read_multiple() {
+        let buf = [2u8 | CONTINUATION_BIT, 1u8, 1u8];
+
+        let mut readable = &buf[..];
+        assert_eq!(
+            read::unsigned(&mut readable).expect("Should read first number"),
+            130u128
+        );
+        assert_eq!(
+            read::unsigned(&mut readable).expect("Should read first number"),
+            1u128
+        );
+    }
+
+    #[test]
+    fn test_read_multiple_with_overflow() {
+        let buf = [
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b1111_1111,
+            0b0111_1111, // Overflow!
+            0b1110_0100,
+            0b1110_0000,
+            0b0000_0010, // 45156
+        ];
+        let mut readable = &buf[..];
+
+        assert!(matches!(
+            read::unsigned(&mut readable).expect_err("Should fail with Error::Overflow"),
+            read::Error::Overflow
+        ));
+        assert_eq!(
+            read::unsigned(&mut readable).expect("Should succeed with correct value"),
+            45156
+        );
+    }
+}
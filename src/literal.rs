@@ -0,0 +1,270 @@
+//! Parsing of the numbers in the expressions given to the encode commands, and of numbers on
+//! their own for `--big` and numeric options.
+//!
+//! Numbers can be written as:
+//!
+//! - Decimal: `624485`
+//! - Hexadecimal, octal or binary with a `0x`, `0o` or `0b` prefix: `0x98765`, `0o2303545`,
+//!   `0b1001_1000`
+//! - With `_` separating digits: `1_000_000`, `0xffff_ffff`
+//! - With a `+` or `-` sign: `+5`, `-0x80`
+//!
+//! Whitespace around the number is ignored.
+
+use super::*;
+
+use std::ops::Range;
+
+/// A number split into its parts, with the digits checked but not yet added up.
+struct Literal<'a> {
+    input: &'a str,
+    negative: bool,
+    /// Location of the sign, if any.
+    sign: Option<Range<usize>>,
+    radix: u32,
+    /// Values of the digits, most significant first.
+    digits: Vec<u32>,
+    /// Location of the whole number, without the surrounding whitespace.
+    span: Range<usize>,
+}
+
+fn radix_name(radix: u32) -> &'static str {
+    match radix {
+        2 => "binary",
+        8 => "octal",
+        10 => "decimal",
+        _ => "hexadecimal",
+    }
+}
+
+fn split(input: &str) -> Result<Literal<'_>, Error> {
+    split_span(input, 0..input.len())
+}
+
+/// Splits the number in `input[span]`. Errors point into the whole `input`.
+fn split_span(input: &str, span: Range<usize>) -> Result<Literal<'_>, Error> {
+    let part = &input[span.clone()];
+    let start = span.start + part.len() - part.trim_start().len();
+    let end = span.start + part.trim_end().len();
+    let span = start..end.max(start);
+    let s = &input[span.clone()];
+    if s.is_empty() {
+        return Err(Error::Parse("Expected a number".to_owned()));
+    }
+
+    let (negative, sign) = match s.as_bytes()[0] {
+        b'-' => (true, Some(start..start + 1)),
+        b'+' => (false, Some(start..start + 1)),
+        _ => (false, None),
+    };
+    let mut pos = start + sign.as_ref().map_or(0, |_| 1);
+
+    let rest = &input[pos..end];
+    let radix = match rest.get(..2).map(|prefix| prefix.to_ascii_lowercase()) {
+        Some(prefix) if prefix == "0x" => 16,
+        Some(prefix) if prefix == "0o" => 8,
+        Some(prefix) if prefix == "0b" => 2,
+        _ => 10,
+    };
+    if radix != 10 {
+        pos += 2;
+    }
+
+    let mut digits = vec![];
+    for (i, c) in input[pos..end].char_indices() {
+        let char_span = pos + i..pos + i + c.len_utf8();
+        let error = |msg: String| Err(Error::Parse(msg).at(input, char_span.clone()));
+        match c {
+            // Separators go between digits, or after a prefix like in `0x_ff`.
+            '_' if !digits.is_empty() || radix != 10 => {}
+            '_' => return error("A number can't start with `_`".to_owned()),
+            '+' | '-' => return error(format!("Unexpected sign `{}`", c)),
+            c if c.is_whitespace() => {
+                return error("Unexpected whitespace in number, use `_` to separate digits".into())
+            }
+            c => match c.to_digit(radix) {
+                Some(digit) => digits.push(digit),
+                None => {
+                    return error(format!(
+                        "Invalid digit `{}` in {} number",
+                        c,
+                        radix_name(radix)
+                    ))
+                }
+            },
+        }
+    }
+
+    if digits.is_empty() {
+        return Err(
+            Error::Parse(format!("Expected {} digits", radix_name(radix))).at(input, end..end),
+        );
+    }
+
+    Ok(Literal {
+        input,
+        negative,
+        sign,
+        radix,
+        digits,
+        span,
+    })
+}
+
+impl Literal<'_> {
+    /// Adds up the digits, or returns `None` if the magnitude doesn't fit in a `u128`.
+    fn magnitude(&self) -> Option<u128> {
+        self.digits.iter().try_fold(0u128, |acc, digit| {
+            acc.checked_mul(self.radix as u128)?
+                .checked_add(*digit as u128)
+        })
+    }
+
+    /// The magnitude, or an error if it doesn't fit in a `u128`.
+    fn unsigned_magnitude(&self) -> Result<u128, Error> {
+        self.magnitude().ok_or_else(|| {
+            self.out_of_range(format!(
+                "Number doesn't fit in 128 bits, the largest unsigned value is {}",
+                u128::MAX
+            ))
+        })
+    }
+
+    fn out_of_range(&self, msg: String) -> Error {
+        Error::Overflow(msg).at(self.input, self.span.clone())
+    }
+}
+
+/// Parses a number in any of the notations in the module documentation as a `u128`.
+pub fn parse_unsigned(s: &str) -> Result<u128, Error> {
+    let literal = split(s)?;
+    let magnitude = literal.unsigned_magnitude()?;
+    if literal.negative && magnitude != 0 {
+        let sign = literal.sign.clone().unwrap();
+        return Err(Error::Parse("Unsigned numbers can't be negative".to_owned()).at(s, sign));
+    }
+    Ok(magnitude)
+}
+
+/// Parses the unsigned number in `input[span]`, which must not have a sign, for numbers that are
+/// part of a larger input like the expressions in `expr`. Errors point into the whole `input`.
+pub fn parse_magnitude(input: &str, span: Range<usize>) -> Result<u128, Error> {
+    split_span(input, span)?.unsigned_magnitude()
+}
+
+/// Parses a number of any size, for `--big`.
+pub fn parse_big_unsigned(s: &str) -> Result<BigUint, Error> {
+    let literal = split(s)?;
+    let magnitude = BigUint::from_radix_digits(literal.radix, &literal.digits).unwrap();
+    if literal.negative && magnitude != BigUint::default() {
+        let sign = literal.sign.clone().unwrap();
+        return Err(Error::Parse("Unsigned numbers can't be negative".to_owned()).at(s, sign));
+    }
+    Ok(magnitude)
+}
+
+/// Parses a signed number of any size, for `--big`.
+pub fn parse_big_signed(s: &str) -> Result<BigInt, Error> {
+    let literal = split(s)?;
+    let magnitude = BigUint::from_radix_digits(literal.radix, &literal.digits).unwrap();
+    Ok(BigInt::new(literal.negative, magnitude))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::message_and_span;
+
+    #[test]
+    fn test_notations() {
+        for s in [
+            "624485",
+            "+624485",
+            "0x98765",
+            "0X98765",
+            "0x9_87_65",
+            "0x_98765",
+            "0o2303545",
+            "0b1001_1000_0111_0110_0101",
+            "624_485",
+            "  624485\t",
+        ] {
+            assert_eq!(parse_unsigned(s).unwrap(), 624485, "{}", s);
+        }
+        assert_eq!(parse_unsigned("-0").unwrap(), 0);
+    }
+
+    #[test]
+    fn test_limits() {
+        assert_eq!(
+            parse_unsigned("0xffffffff_ffffffff_ffffffff_ffffffff").unwrap(),
+            u128::MAX
+        );
+        assert_eq!(
+            parse_unsigned("340282366920938463463374607431768211455").unwrap(),
+            u128::MAX
+        );
+
+        let err = parse_unsigned("0x1_00000000_00000000_00000000_00000000").unwrap_err();
+        assert_eq!(err.exit_code(), 4);
+        assert!(
+            err.message().contains("the largest unsigned value is"),
+            "{}",
+            err
+        );
+        assert_eq!(err.location().unwrap().span, 0..39);
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            message_and_span(parse_unsigned("12a4")),
+            ("Invalid digit `a` in decimal number".to_owned(), Some(2..3))
+        );
+        assert_eq!(
+            message_and_span(parse_unsigned("0b102")),
+            ("Invalid digit `2` in binary number".to_owned(), Some(4..5))
+        );
+        assert_eq!(
+            message_and_span(parse_unsigned("0o8")),
+            ("Invalid digit `8` in octal number".to_owned(), Some(2..3))
+        );
+        assert_eq!(
+            message_and_span(parse_unsigned("0x")),
+            ("Expected hexadecimal digits".to_owned(), Some(2..2))
+        );
+        assert_eq!(
+            message_and_span(parse_unsigned("-")),
+            ("Expected decimal digits".to_owned(), Some(1..1))
+        );
+        assert_eq!(message_and_span(parse_unsigned("")).0, "Expected a number");
+        assert_eq!(message_and_span(parse_unsigned("--5")).1, Some(1..2));
+        assert_eq!(message_and_span(parse_unsigned("_5")).1, Some(0..1));
+        assert_eq!(
+            message_and_span(parse_unsigned("1 000")),
+            (
+                "Unexpected whitespace in number, use `_` to separate digits".to_owned(),
+                Some(1..2)
+            )
+        );
+        assert_eq!(
+            message_and_span(parse_unsigned("-5")),
+            ("Unsigned numbers can't be negative".to_owned(), Some(0..1))
+        );
+        assert_eq!(message_and_span(parse_unsigned("5é")).1, Some(1..3));
+    }
+
+    #[test]
+    fn test_big() {
+        let s = "0x1_0000_0000_0000_0000_0000_0000_0000_0000";
+        assert_eq!(
+            parse_big_unsigned(s).unwrap().to_string(),
+            "340282366920938463463374607431768211456"
+        );
+        assert_eq!(
+            parse_big_signed(&format!("-{}", s)).unwrap().to_string(),
+            "-340282366920938463463374607431768211456"
+        );
+        assert!(parse_big_unsigned("-1").is_err());
+    }
+}
@@ -1,760 +1,1485 @@
-#![allow(clippy::from_str_radix_10)]
-
-use std::io;
+mod cli;
+mod error;
+mod explain;
+mod expr;
+mod input;
+mod literal;
+mod output;
+mod repl;
+
+use cli::{Args, Cmd, Invocation, Source};
+use error::Error;
+use input::HexBytes;
+use leb128_repl::big::{BigInt, BigUint};
+use leb128_repl::{low_bits_of_byte, max_encoded_len, read, write, CONTINUATION_BIT, SIGN_BIT};
+use output::{Format, Json};
+
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, IsTerminal, Read, Seek, SeekFrom, Write};
+use std::ops::Range;
+use std::path::Path;
 use std::process::exit;
 
 fn main() {
-    let mut args = std::env::args().skip(1);
-
-    let cmd = match args.next() {
-        Some(str) => match str.as_str() {
-            "es" => Cmd::EncodeSigned,
-            "eu" => Cmd::EncodeUnsigned,
-            "ds" => Cmd::DecodeSigned,
-            "du" => Cmd::DecodeUnsigned,
-            other => {
-                eprintln!("Unrecognized command: {}", other);
-                show_usage();
-                exit(1);
+    match cli::parse(std::env::args().skip(1)) {
+        Ok(Invocation::Empty) => {
+            if let Err(err) = repl::run() {
+                eprintln!("error: {}", err);
+                exit(err.exit_code());
             }
-        },
-        None => {
-            show_usage();
-            return;
         }
-    };
-
-    let arg = match args.next() {
-        Some(arg) => arg,
-        None => {
-            eprintln!("Argument to command {} is missing.", cmd_str(cmd));
-            show_usage();
-            exit(1);
+        Ok(Invocation::Help(None)) => print!("{}", cli::usage()),
+        Ok(Invocation::Help(Some(cmd))) => print!("{}", cli::cmd_usage(cmd)),
+        Ok(Invocation::Version) => println!("{}", cli::version()),
+        Ok(Invocation::Run(args)) => {
+            if args.source == Source::Stdin && io::stdin().is_terminal() {
+                usage_error(cli::missing_argument(args.cmd));
+            }
+            exit(run_cmd(&args));
         }
-    };
+        Err(err) => usage_error(err),
+    }
+}
 
-    if let Some(arg) = args.next() {
-        eprintln!("Extra argument passed: {}", arg);
-        show_usage();
-        exit(1);
+fn usage_error(err: cli::UsageError) -> ! {
+    eprintln!("error: {}\n", err);
+    match err.cmd {
+        Some(cmd) => eprint!("{}", cli::cmd_usage(cmd)),
+        None => eprint!("{}", cli::usage()),
     }
+    exit(error::EXIT_USAGE);
+}
+
+/// Runs a command, reporting errors on stderr. Returns the exit code.
+fn run_cmd(args: &Args) -> i32 {
+    let result = match &args.source {
+        Source::Args(inputs) if args.cmd.is_encode() && (inputs.len() > 1 || args.concat) => {
+            return run_inputs(args, "argument", inputs.iter().cloned().map(Ok));
+        }
+        Source::Args(inputs) => run_single(args, &inputs.join(" ")),
+        // A dump spans multiple lines, parse the whole input at once.
+        Source::Stdin if args.dump => io::read_to_string(io::stdin())
+            .map_err(Error::from)
+            .and_then(|input| run_single(args, &input)),
+        Source::Stdin => return run_batch(args, io::stdin().lock()),
+        Source::Binary(path) => {
+            let input = path.display().to_string();
+            let result = decode_binary(args, path);
+            if let (true, Ok(Output::Decoded { hex, base, .. })) = (args.explain, &result) {
+                let signed = args.cmd == Cmd::DecodeSigned;
+                let explanation =
+                    explain::decode(&hex.bytes, *base, signed, args.bits, args.max_values());
+                if let Err(e) = println_value(explanation) {
+                    return e.exit_code();
+                }
+            }
+            if args.json {
+                print_json(args, &input, result)
+            } else {
+                result.and_then(|output| print_output(args, output))
+            }
+        }
+        Source::File(path) => {
+            let file = open_file(path);
+            match file {
+                Ok(file) if args.dump => io::read_to_string(file)
+                    .map_err(Error::from)
+                    .and_then(|input| run_single(args, &input)),
+                Ok(file) => return run_batch(args, BufReader::new(file)),
+                Err(e) => Err(e),
+            }
+        }
+    };
 
-    match cmd {
-        Cmd::EncodeSigned => encode_signed(&arg),
-        Cmd::EncodeUnsigned => encode_unsigned(&arg),
-        Cmd::DecodeSigned => decode_signed(&arg),
-        Cmd::DecodeUnsigned => decode_unsigned(&arg),
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            err.exit_code()
+        }
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-enum Cmd {
-    EncodeSigned,
-    EncodeUnsigned,
-    DecodeSigned,
-    DecodeUnsigned,
+fn open_file(path: &Path) -> Result<File, Error> {
+    File::open(path).map_err(|e| {
+        Error::Io(io::Error::new(
+            e.kind(),
+            format!("{}: {}", path.display(), e),
+        ))
+    })
 }
 
-fn cmd_str(cmd: Cmd) -> &'static str {
-    match cmd {
-        Cmd::EncodeSigned => "es",
-        Cmd::EncodeUnsigned => "eu",
-        Cmd::DecodeSigned => "ds",
-        Cmd::DecodeUnsigned => "du",
+fn run_single(args: &Args, input: &str) -> Result<(), Error> {
+    if args.json {
+        return print_json(args, input, run_one(args, input));
     }
-}
 
-fn show_usage() {
-    // TODO
+    if args.explain {
+        print_explanation(args, input)?;
+    }
+
+    print_output(args, run_one(args, input)?)
 }
 
-fn encode_signed(mut s: &str) {
-    let negative = if s.starts_with('-') {
-        s = &s[1..];
-        true
-    } else {
-        false
+/// Prints the `--explain` walkthrough for `input`. Inputs that can't be parsed, and values to
+/// encode that don't fit in `--bits`, are skipped, the error is reported when running the
+/// command.
+fn print_explanation(args: &Args, input: &str) -> Result<(), Error> {
+    let explanation = match args.cmd {
+        Cmd::EncodeSigned => expr::eval_signed(input)
+            .ok()
+            .filter(|i| write::signed_bits(&mut io::sink(), *i, args.bits).is_ok())
+            .map(explain::encode_signed),
+        Cmd::EncodeUnsigned => expr::eval_unsigned(input)
+            .ok()
+            .filter(|i| write::unsigned_bits(&mut io::sink(), *i, args.bits).is_ok())
+            .map(explain::encode_unsigned),
+        Cmd::DecodeSigned | Cmd::DecodeUnsigned => parse_bytes(args, input).ok().map(|hex| {
+            let signed = args.cmd == Cmd::DecodeSigned;
+            explain::decode(&hex.bytes, 0, signed, args.bits, args.max_values())
+        }),
+        Cmd::DecodeBoth => unreachable!("--explain is not accepted by decode-both"),
     };
 
-    let mut i = if s.starts_with("0x") || s.starts_with("0X") {
-        i128::from_str_radix(&s[2..], 16).unwrap()
-    } else {
-        i128::from_str_radix(s, 10).unwrap()
-    };
+    match explanation {
+        Some(explanation) => println_value(explanation),
+        None => Ok(()),
+    }
+}
 
-    if negative {
-        i = -i;
+fn print_output(args: &Args, output: Output) -> Result<(), Error> {
+    match output {
+        Output::Encoded { bytes, .. } => print_encoded(args, &bytes),
+        Output::Decoded {
+            hex,
+            base,
+            values,
+            error,
+        } => {
+            if args.sequence() {
+                for decoded in &values {
+                    println_value(decoded)?;
+                    warn_non_canonical("", decoded);
+                }
+            } else {
+                let decoded = &values[0];
+                println_value(&decoded.value)?;
+                warn_non_canonical("", decoded);
+                if let Source::Binary(_) = args.source {
+                    println_value(format_args!(
+                        "{} bytes read at offset {}.",
+                        decoded.len, decoded.offset
+                    ))?;
+                } else {
+                    let n_read = decoded.offset + decoded.len - base;
+                    print_rest(&hex.bytes, &hex.bytes[n_read..])?;
+                }
+            }
+            error.map_or(Ok(()), Err)
+        }
+        Output::Both {
+            hex,
+            base,
+            offset,
+            len,
+            unsigned,
+            signed,
+        } => {
+            print_reading("unsigned", unsigned.as_ref().map(|i| Value::Unsigned(*i)))?;
+            print_reading("signed", signed.as_ref().map(|i| Value::Signed(*i)))?;
+            match (&unsigned, &signed) {
+                (Ok(_), Ok(_)) if readings_differ(&unsigned, &signed) => {
+                    println_value(format_args!(
+                        "The readings differ: the sign bit (0x{:02x}) of the last byte is set.",
+                        SIGN_BIT
+                    ))?
+                }
+                (Ok(_), Ok(_)) => println_value(format_args!(
+                    "The readings are the same: the sign bit (0x{:02x}) of the last byte is clear.",
+                    SIGN_BIT
+                ))?,
+                (Ok(_), Err(_)) => println_value("Only the unsigned reading is valid.")?,
+                (Err(_), _) => println_value("Only the signed reading is valid.")?,
+            }
+
+            if let Source::Binary(_) = args.source {
+                println_value(format_args!("{} bytes read at offset {}.", len, offset))?;
+            } else {
+                print_rest(&hex.bytes, &hex.bytes[offset + len - base..])?;
+            }
+            Ok(())
+        }
     }
+}
 
-    let mut buf = [0u8; 1024];
-    let mut writable = &mut buf[..];
-    let written = write::signed(&mut writable, i).unwrap();
-    for byte in &buf[0..written] {
-        print!("{:02x}", byte);
+/// Whether the readings of `decode-both` are different numbers. `false` if either failed.
+fn readings_differ(unsigned: &Result<u128, Error>, signed: &Result<i128, Error>) -> bool {
+    match (unsigned, signed) {
+        (Ok(unsigned), Ok(signed)) => i128::try_from(*unsigned) != Ok(*signed),
+        _ => false,
     }
-    println!();
 }
 
-fn encode_unsigned(s: &str) {
-    let i = if s.starts_with("0x") || s.starts_with("0X") {
-        u128::from_str_radix(&s[2..], 16).unwrap()
-    } else {
-        u128::from_str_radix(s, 10).unwrap()
+/// Prints one reading of `decode-both`: the value in decimal and hex, and its two's complement
+/// bit patterns at 32 and 64 bits.
+fn print_reading(name: &str, reading: Result<Value, &Error>) -> Result<(), Error> {
+    let value = match reading {
+        Ok(value) => value,
+        Err(e) => return println_value(format_args!("{:<9} error: {}", name, e.message())),
     };
 
-    let mut buf = [0u8; 1024];
-    let mut writable = &mut buf[..];
-    let written = write::unsigned(&mut writable, i).unwrap();
-    for byte in &buf[0..written] {
-        print!("{:02x}", byte);
-    }
-    println!();
-}
-
-fn decode_signed(s: &str) {
-    let bytes: Vec<u8> = s
-        .trim()
-        .split(' ')
-        .map(|byte_str| {
-            u8::from_str_radix(byte_str, 16)
-                .unwrap_or_else(|_| panic!("Invalid hex byte: {:?}", byte_str))
-        })
-        .collect();
-    let mut readable = &bytes[..];
-    let i = read::signed(&mut readable).unwrap();
-    println!("{}", i);
-    print_rest(&bytes, readable);
-}
-
-fn decode_unsigned(s: &str) {
-    let bytes: Vec<u8> = s
-        .trim()
-        .split(' ')
-        .map(|byte_str| {
-            u8::from_str_radix(byte_str, 16)
-                .unwrap_or_else(|_| panic!("Invalid hex byte: {:?}", byte_str))
-        })
-        .collect();
-    let mut readable = &bytes[..];
-    let i = read::unsigned(&mut readable).unwrap();
-    println!("{}", i);
-    print_rest(&bytes, readable);
-}
-
-fn print_rest(input_bytes: &[u8], unread_bytes: &[u8]) {
-    let n_read = input_bytes.len() - unread_bytes.len();
-    println!("{} bytes read. Unread bytes:", n_read);
-    for byte in unread_bytes {
-        print!("{:02x}", byte);
+    println_value(format_args!("{:<9} {}", name, value))?;
+    println_value(format_args!("{:<9} {:<7} {}", "", "hex", value_hex(&value)))?;
+    for bits in [32, 64] {
+        let pattern = match twos_complement(&value, bits) {
+            Some(pattern) => {
+                let sign_extended = if pattern >> (bits - 1) & 1 == 1 {
+                    pattern as i128 - (1 << bits)
+                } else {
+                    pattern as i128
+                };
+                format!(
+                    "0x{:0width$x}  i{} {}  u{} {}",
+                    pattern,
+                    bits,
+                    sign_extended,
+                    bits,
+                    pattern,
+                    width = bits as usize / 4
+                )
+            }
+            None => "out of range".to_owned(),
+        };
+        println_value(format_args!(
+            "{:<9} {:<7} {}",
+            "",
+            format!("{}-bit", bits),
+            pattern
+        ))?;
     }
-    println!();
+    Ok(())
 }
 
-////////////////////////////////////////////////////////////////////////////////////////////////////
+/// `value` in hexadecimal, with a `-` for negative values.
+fn value_hex(value: &Value) -> String {
+    match value {
+        Value::Signed(i) if *i < 0 => format!("-0x{:x}", i.unsigned_abs()),
+        Value::Signed(i) => format!("0x{:x}", i),
+        Value::Unsigned(i) => format!("0x{:x}", i),
+        Value::Big(i) if i.is_negative() => format!("-{:#x}", i.magnitude()),
+        Value::Big(i) => format!("{:#x}", i.magnitude()),
+    }
+}
 
-const CONTINUATION_BIT: u8 = 1 << 7;
+/// The two's complement bit pattern of `value` in `bits` bits, if it fits in `bits` bits as
+/// either a signed or an unsigned number.
+fn twos_complement(value: &Value, bits: u32) -> Option<u128> {
+    let mask = u128::MAX >> (128 - bits);
+    match value {
+        Value::Unsigned(i) if *i <= mask => Some(*i),
+        Value::Signed(i) if *i >= 0 && *i as u128 <= mask => Some(*i as u128),
+        Value::Signed(i) if *i >> (bits - 1) == -1 => Some(*i as u128 & mask),
+        _ => None,
+    }
+}
 
-const SIGN_BIT: u8 = 1 << 6;
+/// Runs the command on each non-empty line of `reader`, printing one result per line.
+fn run_batch<R: BufRead>(args: &Args, reader: R) -> i32 {
+    run_inputs(
+        args,
+        "line",
+        reader.lines().map(|line| line.map_err(Error::from)),
+    )
+}
 
-fn low_bits_of_byte(byte: u8) -> u8 {
-    byte & !CONTINUATION_BIT
+/// Runs the command on each non-empty input, printing one result per input, or with `--concat`
+/// the encodings of all inputs as one byte stream at the end. Returns the exit code of the first
+/// error, see `run_each`.
+fn run_inputs<I>(args: &Args, what: &str, inputs: I) -> i32
+where
+    I: Iterator<Item = Result<String, Error>>,
+{
+    let mut concatenated = vec![];
+    let mut exit_code = run_each(args, what, inputs, |input_no, input| {
+        if args.concat {
+            concat_input(args, input, &mut concatenated)
+        } else {
+            run_batch_line(args, input_no, input)
+        }
+    });
+
+    // A bad input leaves the stream incomplete, don't print it.
+    if args.concat && exit_code == 0 {
+        if let Err(err) = print_encoded(args, &concatenated) {
+            eprintln!("error: {}", err);
+            exit_code = err.exit_code();
+        }
+    }
+
+    exit_code
 }
 
-fn low_bits_of_u128(val: u128) -> u8 {
-    let byte = val & (std::u8::MAX as u128);
-    low_bits_of_byte(byte as u8)
+/// Calls `run` with the number and the trimmed text of each input. Stops at the first bad input
+/// unless `--keep-going` was passed, errors are prefixed with `what` and the number of the input,
+/// e.g. `line 3: `. Returns the exit code of the first error.
+fn run_each<I, F>(args: &Args, what: &str, inputs: I, mut run: F) -> i32
+where
+    I: Iterator<Item = Result<String, Error>>,
+    F: FnMut(usize, &str) -> Result<(), Error>,
+{
+    let mut exit_code = 0;
+
+    for (input_idx, input) in inputs.enumerate() {
+        let input_no = input_idx + 1;
+        let result = input.and_then(|input| run(input_no, input.trim()));
+
+        if let Err(err) = result {
+            eprintln!("error: {} {}: {}", what, input_no, err);
+            if exit_code == 0 {
+                exit_code = err.exit_code();
+            }
+            // I/O errors are not specific to the input, no point in continuing.
+            if !args.keep_going || matches!(err, Error::Io(_)) {
+                break;
+            }
+        }
+    }
+
+    exit_code
 }
 
-/// A module for reading LEB128-encoded signed and unsigned integers.
-mod read {
-    use super::*;
+/// With `--concat`, encodes `input` and appends the encoding to `bytes`.
+fn concat_input(args: &Args, input: &str, bytes: &mut Vec<u8>) -> Result<(), Error> {
+    if input.is_empty() {
+        return Ok(());
+    }
+
+    if args.explain {
+        print_explanation(args, input)?;
+    }
 
-    use std::fmt;
+    match run_one(args, input)? {
+        Output::Encoded { bytes: encoded, .. } => bytes.extend(encoded),
+        _ => unreachable!("--concat is only accepted by the encode commands"),
+    }
+    Ok(())
+}
 
-    /// An error type for reading LEB128-encoded values.
-    #[derive(Debug)]
-    pub enum Error {
-        /// There was an underlying IO error.
-        IoError(io::Error),
+fn run_batch_line(args: &Args, line_no: usize, line: &str) -> Result<(), Error> {
+    if line.is_empty() {
+        return Ok(());
+    }
 
-        /// The number being read is larger than can be represented.
-        Overflow,
+    if args.json {
+        return print_json(args, line, run_one(args, line));
     }
 
-    impl From<io::Error> for Error {
-        fn from(e: io::Error) -> Self {
-            Error::IoError(e)
-        }
+    if args.explain {
+        print_explanation(args, line)?;
     }
 
-    impl fmt::Display for Error {
-        fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
-            match *self {
-                Error::IoError(ref e) => e.fmt(f),
-                Error::Overflow => {
-                    write!(f, "The number being read is larger than can be represented")
-                }
+    match run_one(args, line)? {
+        Output::Encoded { bytes, .. } => print_encoded(args, &bytes),
+        Output::Decoded {
+            hex,
+            base,
+            values,
+            error,
+        } => {
+            let strs: Vec<String> = values.iter().map(|d| d.value.to_string()).collect();
+            println_value(strs.join(" "))?;
+            for decoded in &values {
+                warn_non_canonical(&format!("line {}: ", line_no), decoded);
             }
+            if let Some(error) = error {
+                return Err(error);
+            }
+            let n_read = values.last().map_or(0, |d| d.offset + d.len - base);
+            warn_unread(line_no, hex.bytes.len() - n_read);
+            Ok(())
+        }
+        Output::Both {
+            hex,
+            base,
+            offset,
+            len,
+            unsigned,
+            signed,
+        } => {
+            let reading = |r: Result<String, &Error>| r.unwrap_or_else(|_| "error".to_owned());
+            let differ = if readings_differ(&unsigned, &signed) {
+                " (differ)"
+            } else {
+                ""
+            };
+            println_value(format_args!(
+                "unsigned: {}, signed: {}{}",
+                reading(unsigned.as_ref().map(u128::to_string)),
+                reading(signed.as_ref().map(i128::to_string)),
+                differ
+            ))?;
+            warn_unread(line_no, hex.bytes.len() - (offset + len - base));
+            Ok(())
         }
     }
+}
 
-    impl std::error::Error for Error {
-        fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
-            match *self {
-                Error::IoError(ref e) => Some(e),
-                Error::Overflow => None,
-            }
+fn warn_unread(line_no: usize, n_unread: usize) {
+    if n_unread != 0 {
+        eprintln!("warning: line {}: {} unread bytes", line_no, n_unread);
+    }
+}
+
+/// Result of running a command on one input.
+enum Output {
+    Encoded {
+        value: Value,
+        bytes: Vec<u8>,
+    },
+    Decoded {
+        hex: HexBytes,
+        /// Offset of `hex.bytes[0]` in the input. Non-zero when decoding a binary file at an
+        /// offset.
+        base: usize,
+        /// With `--all` or `--count` all or `count` values in `hex`, otherwise only the first
+        /// one.
+        values: Vec<Decoded>,
+        /// With `--all`, the error that stopped decoding before the end of `hex`.
+        error: Option<Error>,
+    },
+    /// The unsigned and signed readings of the `len` bytes at `offset`, for `decode-both`.
+    Both {
+        hex: HexBytes,
+        base: usize,
+        offset: usize,
+        len: usize,
+        unsigned: Result<u128, Error>,
+        signed: Result<i128, Error>,
+    },
+}
+
+/// A value decoded from `len` bytes starting at `offset` of the input.
+struct Decoded {
+    value: Value,
+    offset: usize,
+    len: usize,
+    /// The minimal encoding of `value`. Shorter than `len` when the input is not minimal.
+    canonical: Vec<u8>,
+}
+
+impl Decoded {
+    fn new(args: &Args, value: Value, offset: usize, len: usize) -> Decoded {
+        let canonical = canonical_bytes(args.cmd, &value);
+        Decoded {
+            value,
+            offset,
+            len,
+            canonical,
         }
     }
 
-    pub fn unsigned<R>(r: &mut R) -> Result<u128, Error>
-    where
-        R: ?Sized + io::Read,
-    {
-        let mut result = 0;
-        let mut shift = 0;
+    /// Number of bytes of the input beyond the minimal encoding of the value.
+    fn redundant(&self) -> usize {
+        self.len - self.canonical.len()
+    }
+}
 
-        loop {
-            let mut buf = [0];
-            r.read_exact(&mut buf)?;
+/// Warns about a value that is valid but not minimally encoded. `prefix` is printed after
+/// `warning: `, e.g. the line number.
+fn warn_non_canonical(prefix: &str, decoded: &Decoded) {
+    let redundant = decoded.redundant();
+    if redundant == 0 {
+        return;
+    }
+    eprintln!(
+        "warning: {}value at offset {} is not minimal, {} of its {} bytes are redundant. \
+         Canonical form: {}",
+        prefix,
+        decoded.offset,
+        redundant,
+        decoded.len,
+        output::format_bytes(&decoded.canonical, Format::Spaced, false)
+    );
+}
 
-            if shift == 126 && buf[0] != 0x00 && buf[0] != 0x01 {
-                while buf[0] & CONTINUATION_BIT != 0 {
-                    r.read_exact(&mut buf)?;
-                }
-                return Err(Error::Overflow);
-            }
+impl fmt::Display for Decoded {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        let unit = if self.len == 1 { "byte" } else { "bytes" };
+        write!(f, "{}: {} ({} {})", self.offset, self.value, self.len, unit)
+    }
+}
 
-            let low_bits = low_bits_of_byte(buf[0]) as u128;
-            result |= low_bits << shift;
+#[derive(Debug, Clone)]
+enum Value {
+    Signed(i128),
+    Unsigned(u128),
+    /// With `--big`, signed or unsigned.
+    Big(BigInt),
+}
 
-            if buf[0] & CONTINUATION_BIT == 0 {
-                return Ok(result);
-            }
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Value::Signed(i) => i.fmt(f),
+            Value::Unsigned(i) => i.fmt(f),
+            Value::Big(i) => i.fmt(f),
+        }
+    }
+}
 
-            shift += 7;
+fn run_one(args: &Args, input: &str) -> Result<Output, Error> {
+    match args.cmd {
+        Cmd::EncodeSigned => encode_signed(args, input),
+        Cmd::EncodeUnsigned => encode_unsigned(args, input),
+        Cmd::DecodeSigned | Cmd::DecodeUnsigned | Cmd::DecodeBoth => decode(args, input),
+    }
+}
+
+/// With `--big` the encode commands take plain numbers: expressions are evaluated with 128-bit
+/// arithmetic, so they can't produce bigger values.
+fn check_big_literal(s: &str) -> Result<(), Error> {
+    match expr::non_literal_span(s) {
+        Some(span) => Err(Error::Parse(
+            "--big takes plain numbers, not expressions: expressions are evaluated in 128 bits"
+                .to_owned(),
+        )
+        .at(s, span)),
+        None => Ok(()),
+    }
+}
+
+fn encode_signed(args: &Args, s: &str) -> Result<Output, Error> {
+    if args.big {
+        check_big_literal(s)?;
+        let i = literal::parse_big_signed(s)?;
+        let mut bytes = vec![];
+        write::signed_big(&mut bytes, &i)?;
+        return Ok(Output::Encoded {
+            value: Value::Big(i),
+            bytes,
+        });
+    }
+
+    let i = expr::eval_signed(s)?;
+    let mut bytes = vec![];
+    write::signed_bits(&mut bytes, i, args.bits).map_err(|e| match e {
+        write::Error::Overflow => {
+            let min = -1i128 << (args.bits - 1);
+            Error::Overflow(format!(
+                "{} doesn't fit in {} bits, signed {}-bit values are {}..={}",
+                i, args.bits, args.bits, min, !min
+            ))
         }
+        e => Error::from(e),
+    })?;
+    if let Some(len) = args.pad {
+        bytes.clear();
+        write::signed_padded(&mut bytes, i, len).map_err(|e| padding_error(i, len, e))?;
     }
+    Ok(Output::Encoded {
+        value: Value::Signed(i),
+        bytes,
+    })
+}
 
-    pub fn signed<R>(r: &mut R) -> Result<i128, Error>
-    where
-        R: ?Sized + io::Read,
-    {
-        let mut result = 0;
-        let mut shift = 0;
-        let size = 128;
-        let mut byte;
+fn encode_unsigned(args: &Args, s: &str) -> Result<Output, Error> {
+    if args.big {
+        check_big_literal(s)?;
+        let i = literal::parse_big_unsigned(s)?;
+        let mut bytes = vec![];
+        write::unsigned_big(&mut bytes, &i)?;
+        return Ok(Output::Encoded {
+            value: Value::Big(BigInt::from(i)),
+            bytes,
+        });
+    }
 
-        loop {
-            let mut buf = [0];
-            r.read_exact(&mut buf)?;
+    let i = expr::eval_unsigned(s)?;
+    let mut bytes = vec![];
+    write::unsigned_bits(&mut bytes, i, args.bits).map_err(|e| match e {
+        write::Error::Overflow => Error::Overflow(format!(
+            "{} doesn't fit in {} bits, unsigned {}-bit values are 0..={}",
+            i,
+            args.bits,
+            args.bits,
+            u128::MAX >> (128 - args.bits)
+        )),
+        e => Error::from(e),
+    })?;
+    if let Some(len) = args.pad {
+        bytes.clear();
+        write::unsigned_padded(&mut bytes, i, len).map_err(|e| padding_error(i, len, e))?;
+    }
+    Ok(Output::Encoded {
+        value: Value::Unsigned(i),
+        bytes,
+    })
+}
 
-            byte = buf[0];
-            if shift == 126 && byte != 0x00 && byte != 0x7f {
-                while buf[0] & CONTINUATION_BIT != 0 {
-                    r.read_exact(&mut buf)?;
-                }
-                return Err(Error::Overflow);
-            }
+/// The error for a `value` that doesn't fit in the `len` bytes given with `--pad`.
+fn padding_error<T: fmt::Display>(value: T, len: usize, e: write::Error) -> Error {
+    match e {
+        write::Error::PaddingTooShort { needed } => Error::Overflow(format!(
+            "{} needs {} bytes, more than the {} given with --pad",
+            value, needed, len
+        )),
+        e => Error::from(e),
+    }
+}
 
-            let low_bits = low_bits_of_byte(byte) as i128;
-            result |= low_bits << shift;
-            shift += 7;
+fn parse_bytes(args: &Args, s: &str) -> Result<HexBytes, Error> {
+    if args.dump {
+        input::parse_dump(s)
+    } else {
+        input::parse_hex(s)
+    }
+}
 
-            if byte & CONTINUATION_BIT == 0 {
+fn decode(args: &Args, s: &str) -> Result<Output, Error> {
+    let hex = parse_bytes(args, s)?;
+    let mut values = vec![];
+    let mut error = None;
+
+    let max_values = args.max_values();
+    let mut readable = &hex.bytes[..];
+    while !readable.is_empty() && max_values.is_none_or(|max| values.len() < max) {
+        let offset = hex.bytes.len() - readable.len();
+        match read_value(args, &mut readable) {
+            Ok(value) => values.push(Decoded::new(
+                args,
+                value,
+                offset,
+                hex.bytes.len() - readable.len() - offset,
+            )),
+            Err(e) => {
+                let end = hex.bytes.len() - readable.len();
+                let e = locate_read_error(args, s, &hex, offset..end, e);
+                if !args.sequence() {
+                    return Err(e);
+                }
+                error = Some(e);
                 break;
             }
         }
+    }
 
-        if shift < size && (SIGN_BIT & byte) == SIGN_BIT {
-            // Sign extend the result.
-            result |= !0 << shift;
-        }
-
-        Ok(result)
+    let output = Output::Decoded {
+        hex,
+        base: 0,
+        values,
+        error,
+    };
+    if args.cmd == Cmd::DecodeBoth {
+        return read_both(args, output, |hex, value, e| {
+            locate_read_error(args, s, hex, value, e)
+        });
     }
+    Ok(output)
 }
 
-mod write {
-    use super::*;
+/// Reads the value found by `decode` or `decode_binary` for `decode-both`, both as unsigned and
+/// as signed. `locate` adds the location of the value in `hex.bytes` to read errors. Fails only
+/// when neither reading succeeds.
+fn read_both<F>(args: &Args, output: Output, locate: F) -> Result<Output, Error>
+where
+    F: Fn(&HexBytes, Range<usize>, read::Error) -> Error,
+{
+    let Output::Decoded {
+        hex, base, values, ..
+    } = output
+    else {
+        return Ok(output);
+    };
 
-    pub fn unsigned<W>(w: &mut W, mut val: u128) -> Result<usize, io::Error>
-    where
-        W: ?Sized + io::Write,
-    {
-        let mut bytes_written = 0;
-        loop {
-            let mut byte = low_bits_of_u128(val);
-            val >>= 7;
-            if val != 0 {
-                // More bytes to come, so set the continuation bit.
-                byte |= CONTINUATION_BIT;
-            }
+    let Decoded { offset, len, .. } = values[0];
+    let value = offset - base..offset + len - base;
+    let bytes = &hex.bytes[value.clone()];
+    let unsigned = read::unsigned_bits(&mut &bytes[..], args.bits);
+    let signed = read::signed_bits(&mut &bytes[..], args.bits);
+
+    let (unsigned, signed) = match (unsigned, signed) {
+        (Err(e), Err(_)) => return Err(locate(&hex, value, e)),
+        (unsigned, signed) => (
+            unsigned.map_err(|e| locate(&hex, value.clone(), e)),
+            signed.map_err(|e| locate(&hex, value.clone(), e)),
+        ),
+    };
 
-            let buf = [byte];
-            w.write_all(&buf)?;
-            bytes_written += 1;
+    Ok(Output::Both {
+        hex,
+        base,
+        offset,
+        len,
+        unsigned,
+        signed,
+    })
+}
 
-            if val == 0 {
-                return Ok(bytes_written);
-            }
+fn read_value<R>(args: &Args, r: &mut R) -> Result<Value, read::Error>
+where
+    R: ?Sized + io::Read,
+{
+    if args.cmd == Cmd::DecodeBoth {
+        // Only finds the end of the value, `read_both` reads it both ways.
+        read::unsigned_big(r).map(|i| Value::Big(BigInt::from(i)))
+    } else if args.big {
+        if args.cmd == Cmd::DecodeSigned {
+            read::signed_big(r).map(Value::Big)
+        } else {
+            read::unsigned_big(r).map(|i| Value::Big(BigInt::from(i)))
+        }
+    } else if args.cmd == Cmd::DecodeSigned {
+        if args.strict {
+            read::signed_bits_strict(r, args.bits).map(Value::Signed)
+        } else {
+            read::signed_bits(r, args.bits).map(Value::Signed)
         }
+    } else if args.strict {
+        read::unsigned_bits_strict(r, args.bits).map(Value::Unsigned)
+    } else {
+        read::unsigned_bits(r, args.bits).map(Value::Unsigned)
     }
+}
 
-    pub fn signed<W>(w: &mut W, mut val: i128) -> Result<usize, io::Error>
-    where
-        W: ?Sized + io::Write,
-    {
-        let mut bytes_written = 0;
-        loop {
-            let mut byte = val as u8;
-            // Keep the sign bit for testing.
-            val >>= 6;
-            let done = val == 0 || val == -1;
-            if done {
-                byte &= !CONTINUATION_BIT;
-            } else {
-                // Remove the sign bit.
-                val >>= 1;
-                // More bytes to come, so set the continuation bit.
-                byte |= CONTINUATION_BIT;
-            }
+/// The minimal encoding of a value decoded with `cmd`.
+fn canonical_bytes(cmd: Cmd, value: &Value) -> Vec<u8> {
+    let mut bytes = vec![];
+    let result = match value {
+        Value::Signed(i) => write::signed(&mut bytes, *i),
+        Value::Unsigned(i) => write::unsigned(&mut bytes, *i),
+        Value::Big(i) if cmd == Cmd::DecodeSigned => write::signed_big(&mut bytes, i),
+        Value::Big(i) => write::unsigned_big(&mut bytes, i.magnitude()),
+    };
+    result.expect("Writing to a Vec can't fail");
+    bytes
+}
 
-            let buf = [byte];
-            w.write_all(&buf)?;
-            bytes_written += 1;
+/// A reader that keeps a copy of the bytes read from it.
+struct Recording<'a, R> {
+    inner: R,
+    bytes: &'a mut Vec<u8>,
+}
 
-            if done {
-                return Ok(bytes_written);
-            }
-        }
+impl<R: io::Read> io::Read for Recording<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.bytes.extend_from_slice(&buf[..n]);
+        Ok(n)
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-
-    use std;
+/// Decodes values from the raw bytes of a file, starting at `--offset`.
+fn decode_binary(args: &Args, path: &Path) -> Result<Output, Error> {
+    decode_seekable(args, open_file(path)?)
+}
 
-    #[test]
-    fn test_low_bits_of_byte() {
-        for i in 0..127 {
-            assert_eq!(i, low_bits_of_byte(i));
-            assert_eq!(i, low_bits_of_byte(i | CONTINUATION_BIT));
+/// Decodes values from the raw bytes of `input`, starting at `--offset`.
+fn decode_seekable<R>(args: &Args, mut input: R) -> Result<Output, Error>
+where
+    R: Read + Seek,
+{
+    input.seek(SeekFrom::Start(args.offset))?;
+    let mut reader = BufReader::new(input.take(args.length.unwrap_or(u64::MAX)));
+
+    let base = args.offset as usize;
+    let mut bytes = vec![];
+    let mut values = vec![];
+    let mut error = None;
+
+    let max_values = args.max_values();
+    while max_values.is_none_or(|max| values.len() < max) {
+        if reader.fill_buf()?.is_empty() {
+            break;
         }
-    }
 
-    #[test]
-    fn test_low_bits_of_u128() {
-        for i in 0u128..127 {
-            assert_eq!(i as u8, low_bits_of_u128(1 << 16 | i));
-            assert_eq!(
-                i as u8,
-                low_bits_of_u128(i << 16 | i | (CONTINUATION_BIT as u128))
-            );
+        let offset = base + bytes.len();
+        let mut recording = Recording {
+            inner: &mut reader,
+            bytes: &mut bytes,
+        };
+        match read_value(args, &mut recording) {
+            Ok(value) => values.push(Decoded::new(
+                args,
+                value,
+                offset,
+                base + bytes.len() - offset,
+            )),
+            Err(e) => {
+                let e = binary_read_error(args, offset, &bytes[offset - base..], e);
+                if !args.sequence() {
+                    return Err(e);
+                }
+                error = Some(e);
+                break;
+            }
         }
     }
 
-    #[test]
-    fn test_read_unsigned() {
-        let buf = [2u8];
-        let mut readable = &buf[..];
-        assert_eq!(
-            2,
-            read::unsigned(&mut readable).expect("Should read number")
-        );
+    if values.is_empty() && error.is_none() && !args.sequence() {
+        return Err(Error::Truncated(format!(
+            "No bytes to decode at offset {}",
+            args.offset
+        )));
+    }
 
-        let buf = [127u8];
-        let mut readable = &buf[..];
-        assert_eq!(
-            127,
-            read::unsigned(&mut readable).expect("Should read number")
-        );
+    let hex = HexBytes {
+        bytes,
+        spans: vec![],
+    };
+    let output = Output::Decoded {
+        hex,
+        base,
+        values,
+        error,
+    };
+    if args.cmd == Cmd::DecodeBoth {
+        return read_both(args, output, |hex, value, e| {
+            binary_read_error(args, base + value.start, &hex.bytes[value], e)
+        });
+    }
+    Ok(output)
+}
 
-        let buf = [CONTINUATION_BIT, 1];
-        let mut readable = &buf[..];
-        assert_eq!(
-            128,
-            read::unsigned(&mut readable).expect("Should read number")
-        );
+/// Describes why the last byte a value can have with `--bits`, `byte`, is rejected.
+fn overflow_reason(args: &Args, byte: u8) -> String {
+    if byte & CONTINUATION_BIT != 0 {
+        format!(
+            "sets continuation bit but {}-bit values are at most {} bytes long",
+            args.bits,
+            max_encoded_len(args.bits)
+        )
+    } else if args.bits == 128 && args.cmd != Cmd::DecodeBoth {
+        "carries bits beyond 128, decode with --big for the exact value".to_owned()
+    } else {
+        format!("carries bits beyond {}", args.bits)
+    }
+}
 
-        let buf = [1u8 | CONTINUATION_BIT, 1];
-        let mut readable = &buf[..];
-        assert_eq!(
-            129,
-            read::unsigned(&mut readable).expect("Should read number")
-        );
+/// Adds the offset of the value to a read error, the binary counterpart of `locate_read_error`.
+/// `bytes` are the bytes of the value read from the file.
+fn binary_read_error(args: &Args, offset: usize, bytes: &[u8], err: read::Error) -> Error {
+    match Error::from(err) {
+        Error::Truncated(_) => {
+            let end = if args.length.is_some() {
+                "the range given with --length"
+            } else {
+                "the file"
+            };
+            Error::Truncated(format!(
+                "Value at offset {} sets continuation bit on its last byte but {} ends there",
+                offset, end
+            ))
+        }
+        Error::Overflow(_) => {
+            let last = max_encoded_len(args.bits) - 1;
+            Error::Overflow(format!(
+                "Value at offset {}: byte at offset {} {}",
+                offset,
+                offset + last,
+                overflow_reason(args, bytes[last])
+            ))
+        }
+        Error::NonCanonical(_) => {
+            let canonical = canonical_of(args, bytes);
+            Error::NonCanonical(format!(
+                "Value at offset {} has {} redundant bytes, the canonical encoding is {}",
+                offset,
+                bytes.len() - canonical.len(),
+                output::format_bytes(&canonical, Format::Spaced, false)
+            ))
+        }
+        err => err,
+    }
+}
 
-        let buf = [2u8 | CONTINUATION_BIT, 1];
-        let mut readable = &buf[..];
-        assert_eq!(
-            130,
-            read::unsigned(&mut readable).expect("Should read number")
-        );
+/// The minimal encoding of the value in `bytes`, which `--strict` rejected.
+fn canonical_of(args: &Args, bytes: &[u8]) -> Vec<u8> {
+    let value = if args.cmd == Cmd::DecodeSigned {
+        read::signed_bits(&mut &bytes[..], args.bits).map(Value::Signed)
+    } else {
+        read::unsigned_bits(&mut &bytes[..], args.bits).map(Value::Unsigned)
+    };
+    canonical_bytes(
+        args.cmd,
+        &value.expect("Only the length of the value was rejected"),
+    )
+}
 
-        let buf = [57u8 | CONTINUATION_BIT, 100];
-        let mut readable = &buf[..];
-        assert_eq!(
-            12857,
-            read::unsigned(&mut readable).expect("Should read number")
-        );
+/// Points a read error of the value in `hex.bytes[value]` at the offending bytes.
+fn locate_read_error(
+    args: &Args,
+    input: &str,
+    hex: &HexBytes,
+    value: Range<usize>,
+    err: read::Error,
+) -> Error {
+    let start = value.start;
+    match Error::from(err) {
+        Error::Truncated(_) => {
+            let last = hex.bytes.len() - 1;
+            Error::Truncated(format!(
+                "byte at offset {} sets continuation bit but input ends here",
+                last
+            ))
+            .at(input, hex.spans[last].clone())
+        }
+        Error::Overflow(_) => {
+            // Only the last byte a value can have is checked for bits beyond `args.bits`.
+            let overflowing = start + max_encoded_len(args.bits) - 1;
+            let reason = overflow_reason(args, hex.bytes[overflowing]);
+            Error::Overflow(format!("byte at offset {} {}", overflowing, reason))
+                .at(input, hex.spans[overflowing].clone())
+        }
+        Error::NonCanonical(_) => {
+            let canonical = canonical_of(args, &hex.bytes[value.clone()]);
+            let first = value.start + canonical.len();
+            let last = value.end - 1;
+            let which = if first == last {
+                format!("byte at offset {} is", first)
+            } else {
+                format!("bytes at offsets {}-{} are", first, last)
+            };
+            Error::NonCanonical(format!(
+                "{} redundant, the canonical encoding is {}",
+                which,
+                output::format_bytes(&canonical, Format::Spaced, false)
+            ))
+            .at(input, hex.spans[first].start..hex.spans[last].end)
+        }
+        err => err,
     }
+}
 
-    #[test]
-    fn test_read_unsigned_thru_dyn_trait() {
-        fn read(r: &mut dyn io::Read) -> u128 {
-            read::unsigned(r).expect("Should read number")
+/// Prints the result of running the command on `input` as a JSON object on a single line, see
+/// `json_output`.
+///
+/// Returns the error of the command, if any, so that it's also reported on stderr and the exit
+/// code reflects it.
+fn print_json(args: &Args, input: &str, result: Result<Output, Error>) -> Result<(), Error> {
+    let (json, error) = json_output(args, input, result);
+    println_value(json)?;
+    error.map_or(Ok(()), Err)
+}
+
+/// The JSON object for the result of running the command on `input`, and the error of the
+/// command if any.
+///
+/// The objects have the following fields. Fields not listed for a command are not included.
+///
+/// - `command`: long name of the command, e.g. `"decode-unsigned"`
+/// - `input`: the input of the command
+/// - `value`: the encoded or decoded value as a decimal string. Not included with `--all` or
+///   `--count`
+/// - `bytes`: the encoded bytes, or the bytes the value was decoded from, as a hex string
+/// - `length`: number of bytes in `bytes`
+/// - `canonical`: decode commands only, the minimal encoding of the value as a hex string. Differs
+///   from `bytes` when the input is not minimal
+/// - `values`: with `--all` or `--count`, an array of `{offset, length, value, bytes, canonical}`
+///   objects
+/// - `unsigned`, `signed`: `decode-both` only, `{value, hex, bits32, bits64, error}` objects for
+///   the two readings. `hex` is the value in hex, `bits32` and `bits64` the two's complement bit
+///   patterns or `null` when out of range, and `error` the message if the reading failed, with
+///   the other fields `null`
+/// - `differ`: `decode-both` only, whether the readings are different numbers, `null` if either
+///   failed
+/// - `unread`: decode commands only, bytes after the decoded values as a hex string. Always `""`
+///   with `--binary`, which doesn't read the file past the decoded values
+/// - `error`: `null`, or `{kind, message, exit_code, span}` where `kind` is one of `parse`,
+///   `truncated`, `overflow`, `non-canonical`, `io`, and `span` is the `[start, end)` byte range
+///   of the offending part of `input` or `null`
+fn json_output(args: &Args, input: &str, result: Result<Output, Error>) -> (Json, Option<Error>) {
+    let mut fields = vec![
+        ("command", Json::string(args.cmd.long_name())),
+        ("input", Json::string(input)),
+    ];
+
+    let hex_string = |bytes: &[u8]| Json::String(output::format_bytes(bytes, Format::Hex, false));
+
+    let error = match result {
+        Ok(Output::Encoded { value, bytes }) => {
+            fields.push(("value", Json::string(value)));
+            fields.push(("bytes", hex_string(&bytes)));
+            fields.push(("length", Json::Number(bytes.len() as i128)));
+            None
         }
+        Ok(Output::Decoded {
+            hex,
+            base,
+            values,
+            error,
+        }) => {
+            let bytes_of = |d: &Decoded| &hex.bytes[d.offset - base..d.offset + d.len - base];
+            if args.sequence() {
+                let values = values
+                    .iter()
+                    .map(|d| {
+                        Json::Object(vec![
+                            ("offset", Json::Number(d.offset as i128)),
+                            ("length", Json::Number(d.len as i128)),
+                            ("value", Json::string(&d.value)),
+                            ("bytes", hex_string(bytes_of(d))),
+                            ("canonical", hex_string(&d.canonical)),
+                        ])
+                    })
+                    .collect();
+                fields.push(("values", Json::Array(values)));
+            } else {
+                let d = &values[0];
+                fields.push(("value", Json::string(&d.value)));
+                fields.push(("bytes", hex_string(bytes_of(d))));
+                fields.push(("canonical", hex_string(&d.canonical)));
+                fields.push(("length", Json::Number(d.len as i128)));
+            }
+            let n_read = values.last().map_or(0, |d| d.offset + d.len - base);
+            fields.push(("unread", hex_string(&hex.bytes[n_read..])));
+            error
+        }
+        Ok(Output::Both {
+            hex,
+            base,
+            offset,
+            len,
+            unsigned,
+            signed,
+        }) => {
+            let differ = match (&unsigned, &signed) {
+                (Ok(_), Ok(_)) => Json::Bool(readings_differ(&unsigned, &signed)),
+                _ => Json::Null,
+            };
+            let value = offset - base..offset + len - base;
+            fields.push(("bytes", hex_string(&hex.bytes[value.clone()])));
+            fields.push(("length", Json::Number(len as i128)));
+            fields.push(("unsigned", reading_json(unsigned.map(Value::Unsigned))));
+            fields.push(("signed", reading_json(signed.map(Value::Signed))));
+            fields.push(("differ", differ));
+            fields.push(("unread", hex_string(&hex.bytes[value.end..])));
+            None
+        }
+        Err(error) => Some(error),
+    };
 
-        let buf = [0u8];
+    let error_json = match &error {
+        None => Json::Null,
+        Some(error) => Json::Object(vec![
+            ("kind", Json::string(error.kind())),
+            ("message", Json::String(error.message())),
+            ("exit_code", Json::Number(error.exit_code() as i128)),
+            (
+                "span",
+                match error.location() {
+                    Some(location) => Json::Array(vec![
+                        Json::Number(location.span.start as i128),
+                        Json::Number(location.span.end as i128),
+                    ]),
+                    None => Json::Null,
+                },
+            ),
+        ]),
+    };
+    fields.push(("error", error_json));
 
-        let mut readable = &buf[..];
-        assert_eq!(0, read(&mut readable));
+    (Json::Object(fields), error)
+}
 
-        let mut readable = io::Cursor::new(buf);
-        assert_eq!(0, read(&mut readable));
+/// One reading of `decode-both` in `--json` output.
+fn reading_json(reading: Result<Value, Error>) -> Json {
+    let bits_json = |value: &Value, bits: u32| match twos_complement(value, bits) {
+        Some(pattern) => Json::String(format!("0x{:0width$x}", pattern, width = bits as usize / 4)),
+        None => Json::Null,
+    };
+    match reading {
+        Ok(value) => Json::Object(vec![
+            ("value", Json::string(&value)),
+            ("hex", Json::String(value_hex(&value))),
+            ("bits32", bits_json(&value, 32)),
+            ("bits64", bits_json(&value, 64)),
+            ("error", Json::Null),
+        ]),
+        Err(e) => Json::Object(vec![
+            ("value", Json::Null),
+            ("hex", Json::Null),
+            ("bits32", Json::Null),
+            ("bits64", Json::Null),
+            ("error", Json::String(e.message())),
+        ]),
     }
+}
 
-    // Examples from the DWARF 4 standard, section 7.6, figure 23.
-    #[test]
-    fn test_read_signed() {
-        let buf = [2u8];
-        let mut readable = &buf[..];
-        assert_eq!(2, read::signed(&mut readable).expect("Should read number"));
+fn println_value<T: fmt::Display>(value: T) -> Result<(), Error> {
+    let mut out = io::stdout().lock();
+    writeln!(out, "{}", value)?;
+    Ok(())
+}
 
-        let buf = [0x7eu8];
-        let mut readable = &buf[..];
-        assert_eq!(-2, read::signed(&mut readable).expect("Should read number"));
+fn print_bytes(bytes: &[u8]) -> Result<(), Error> {
+    let mut out = io::stdout().lock();
+    for byte in bytes {
+        write!(out, "{:02x}", byte)?;
+    }
+    writeln!(out)?;
+    Ok(())
+}
+
+fn print_encoded(args: &Args, bytes: &[u8]) -> Result<(), Error> {
+    let mut out = io::stdout().lock();
+    if args.format == Format::Raw {
+        out.write_all(bytes)?;
+    } else {
+        let formatted = output::format_bytes(bytes, args.format, args.upper);
+        writeln!(out, "{}", formatted)?;
+    }
+    Ok(())
+}
+
+fn print_rest(input_bytes: &[u8], unread_bytes: &[u8]) -> Result<(), Error> {
+    let n_read = input_bytes.len() - unread_bytes.len();
+    println_value(format_args!("{} bytes read. Unread bytes:", n_read))?;
+    print_bytes(unread_bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use error::message_and_span;
 
-        let buf = [127u8 | CONTINUATION_BIT, 0];
-        let mut readable = &buf[..];
+    #[test]
+    fn test_twos_complement() {
+        assert_eq!(twos_complement(&Value::Signed(-2), 32), Some(0xffff_fffe));
         assert_eq!(
-            127,
-            read::signed(&mut readable).expect("Should read number")
+            twos_complement(&Value::Signed(-2), 64),
+            Some(u64::MAX as u128 - 1)
         );
-
-        let buf = [1u8 | CONTINUATION_BIT, 0x7f];
-        let mut readable = &buf[..];
         assert_eq!(
-            -127,
-            read::signed(&mut readable).expect("Should read number")
+            twos_complement(&Value::Signed(i32::MIN as i128), 32),
+            Some(1 << 31)
         );
-
-        let buf = [CONTINUATION_BIT, 1];
-        let mut readable = &buf[..];
         assert_eq!(
-            128,
-            read::signed(&mut readable).expect("Should read number")
+            twos_complement(&Value::Signed(i32::MIN as i128 - 1), 32),
+            None
         );
-
-        let buf = [CONTINUATION_BIT, 0x7f];
-        let mut readable = &buf[..];
         assert_eq!(
-            -128,
-            read::signed(&mut readable).expect("Should read number")
+            twos_complement(&Value::Signed(u32::MAX as i128), 32),
+            Some(0xffff_ffff)
         );
-
-        let buf = [1u8 | CONTINUATION_BIT, 1];
-        let mut readable = &buf[..];
         assert_eq!(
-            129,
-            read::signed(&mut readable).expect("Should read number")
+            twos_complement(&Value::Unsigned(u32::MAX as u128), 32),
+            Some(0xffff_ffff)
         );
-
-        let buf = [0x7fu8 | CONTINUATION_BIT, 0x7e];
-        let mut readable = &buf[..];
+        assert_eq!(twos_complement(&Value::Unsigned(1 << 32), 32), None);
         assert_eq!(
-            -129,
-            read::signed(&mut readable).expect("Should read number")
+            twos_complement(&Value::Unsigned(1 << 32), 64),
+            Some(1 << 32)
         );
-    }
-
-    #[test]
-    fn test_read_signed_thru_dyn_trait() {
-        fn read(r: &mut dyn io::Read) -> i128 {
-            read::signed(r).expect("Should read number")
-        }
 
-        let buf = [0u8];
-
-        let mut readable = &buf[..];
-        assert_eq!(0, read(&mut readable));
-
-        let mut readable = io::Cursor::new(buf);
-        assert_eq!(0, read(&mut readable));
+        assert_eq!(value_hex(&Value::Signed(-2)), "-0x2");
+        assert_eq!(value_hex(&Value::Unsigned(624485)), "0x98765");
     }
 
-    #[test]
-    fn test_read_signed_127_bits() {
-        let buf = [
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            CONTINUATION_BIT,
-            0x40,
-        ];
-        let mut readable = &buf[..];
-        assert_eq!(
-            -0x400000000000000000000000000000,
-            read::signed(&mut readable).expect("Should read number")
-        );
-    }
-
-    #[test]
-    fn test_read_unsigned_not_enough_data() {
-        let buf = [CONTINUATION_BIT];
-        let mut readable = &buf[..];
-        match read::unsigned(&mut readable) {
-            Err(read::Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
-            otherwise => panic!("Unexpected: {:?}", otherwise),
+    fn parse_args(words: &[&str]) -> Args {
+        match cli::parse(words.iter().map(|word| word.to_string())) {
+            Ok(Invocation::Run(args)) => args,
+            other => panic!("Unexpected: {:?}", other),
         }
     }
 
-    #[test]
-    fn test_read_signed_not_enough_data() {
-        let buf = [CONTINUATION_BIT];
-        let mut readable = &buf[..];
-        match read::signed(&mut readable) {
-            Err(read::Error::IoError(e)) => assert_eq!(e.kind(), io::ErrorKind::UnexpectedEof),
-            otherwise => panic!("Unexpected: {:?}", otherwise),
-        }
+    /// Runs `decode_binary` on `bytes` rather than a file.
+    fn decode_bytes(words: &[&str], bytes: &[u8]) -> Result<Output, Error> {
+        let mut words = words.to_vec();
+        words.extend(["--binary", "test.bin"]);
+        decode_seekable(&parse_args(&words), io::Cursor::new(bytes))
     }
 
-    #[test]
-    fn test_write_unsigned_not_enough_space() {
-        let mut buf = [0; 1];
-        let mut writable = &mut buf[..];
-        match write::unsigned(&mut writable, 128) {
-            Err(e) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
-            otherwise => panic!("Unexpected: {:?}", otherwise),
+    /// The offset, length and value of each decoded value, and the error that stopped decoding.
+    fn decoded(result: Result<Output, Error>) -> (Vec<(usize, usize, String)>, Option<String>) {
+        match result {
+            Ok(Output::Decoded { values, error, .. }) => (
+                values
+                    .iter()
+                    .map(|d| (d.offset, d.len, d.value.to_string()))
+                    .collect(),
+                error.map(|e| e.to_string()),
+            ),
+            Ok(_) => panic!("Unexpected output"),
+            Err(e) => panic!("Unexpected error: {}", e),
         }
     }
 
-    #[test]
-    fn test_write_signed_not_enough_space() {
-        let mut buf = [0; 1];
-        let mut writable = &mut buf[..];
-        match write::signed(&mut writable, 128) {
-            Err(e) => assert_eq!(e.kind(), io::ErrorKind::WriteZero),
-            otherwise => panic!("Unexpected: {:?}", otherwise),
-        }
+    /// Runs `run_each` with `run_one` on `inputs`. Returns the exit code and the numbers of the
+    /// inputs that were run.
+    fn run_lines(words: &[&str], inputs: Vec<Result<&str, Error>>) -> (i32, Vec<usize>) {
+        let args = parse_args(words);
+        let inputs = inputs.into_iter().map(|input| input.map(str::to_owned));
+        let mut run = vec![];
+        let exit_code = run_each(&args, "line", inputs, |input_no, input| {
+            run.push(input_no);
+            run_one(&args, input).map(|_| ())
+        });
+        (exit_code, run)
     }
 
     #[test]
-    fn test_write_unsigned_thru_dyn_trait() {
-        fn write(w: &mut dyn io::Write, val: u128) -> usize {
-            write::unsigned(w, val).expect("Should write number")
-        }
-        let mut buf = [0u8; 1];
-
-        let mut writable = &mut buf[..];
-        assert_eq!(write(&mut writable, 0), 1);
-        assert_eq!(buf[0], 0);
-
-        let mut writable = Vec::from(&buf[..]);
-        assert_eq!(write(&mut writable, 0), 1);
-        assert_eq!(buf[0], 0);
+    fn test_run_each() {
+        assert_eq!(
+            run_lines(&["eu"], vec![Ok("1"), Ok(" 300\t"), Ok("0xff")]),
+            (0, vec![1, 2, 3])
+        );
+        assert_eq!(
+            run_lines(&["eu"], vec![Ok("1"), Ok("x"), Ok("300")]),
+            (2, vec![1, 2])
+        );
+        assert_eq!(
+            run_lines(&["du"], vec![Ok("80"), Ok("e58e26")]),
+            (3, vec![1])
+        );
+        assert_eq!(
+            run_lines(
+                &["eu"],
+                vec![Ok("u8::MAX"), Ok("1 << 35"), Ok("(1 + 2) * 3")]
+            ),
+            (0, vec![1, 2, 3])
+        );
     }
 
     #[test]
-    fn test_write_signed_thru_dyn_trait() {
-        fn write(w: &mut dyn io::Write, val: i128) -> usize {
-            write::signed(w, val).expect("Should write number")
-        }
-        let mut buf = [0u8; 1];
-
-        let mut writable = &mut buf[..];
-        assert_eq!(write(&mut writable, 0), 1);
-        assert_eq!(buf[0], 0);
+    fn test_run_each_keep_going() {
+        // The exit code is the one of the first error.
+        let overflow = "ff ".repeat(19) + "7f";
+        assert_eq!(
+            run_lines(
+                &["du", "--keep-going"],
+                vec![Ok(&overflow), Ok("80"), Ok("e58e26")]
+            ),
+            (4, vec![1, 2, 3])
+        );
+        assert_eq!(
+            run_lines(&["eu", "-k"], vec![Ok("x"), Ok("-1"), Ok("5")]),
+            (2, vec![1, 2, 3])
+        );
+        assert_eq!(
+            run_lines(
+                &["eu", "--keep-going"],
+                vec![Ok("1"), Ok("u128::MAX + 1"), Ok("1 << 200"), Ok("5")]
+            ),
+            (4, vec![1, 2, 3, 4])
+        );
+        assert_eq!(
+            run_lines(&["es", "-k", "--bits", "8"], vec![Ok("200"), Ok("x")]),
+            (4, vec![1, 2])
+        );
 
-        let mut writable = Vec::from(&buf[..]);
-        assert_eq!(write(&mut writable, 0), 1);
-        assert_eq!(buf[0], 0);
+        // Reading stops at I/O errors even with --keep-going.
+        let io_error = Error::Io(io::ErrorKind::InvalidData.into());
+        assert_eq!(
+            run_lines(&["eu", "-k"], vec![Ok("1"), Err(io_error), Ok("2")]),
+            (5, vec![1])
+        );
     }
 
     #[test]
-    fn dogfood_signed() {
-        fn inner(i: i128) {
-            let mut buf = [0u8; 1024];
-
-            let mut writable = &mut buf[..];
-            write::signed(&mut writable, i).expect("Should write signed number");
-
-            let mut readable = &buf[..];
-            let result = read::signed(&mut readable).expect("Should be able to read it back again");
-            assert_eq!(i, result);
-        }
-        for i in -513..513 {
-            inner(i);
+    fn test_encode_big() {
+        let args = parse_args(&["eu", "--big", "1"]);
+        let big = format!("0x1{}", "0".repeat(50));
+        match run_one(&args, &big) {
+            Ok(Output::Encoded { value, bytes }) => {
+                assert_eq!(
+                    value.to_string(),
+                    "1606938044258990275541962092341162602522202993782792835301376"
+                );
+                assert_eq!(bytes.len(), 29);
+            }
+            _ => panic!("Unexpected output"),
         }
-        inner(std::i64::MIN as i128);
+
+        // Expressions are evaluated in 128 bits, so they are rejected rather than misread.
+        let err = run_one(&args, "1 << 200").err().unwrap();
+        assert_eq!(
+            err.message(),
+            "--big takes plain numbers, not expressions: expressions are evaluated in 128 bits"
+        );
+        assert_eq!(err.location().unwrap().span, 2..4);
+        let args = parse_args(&["es", "--big", "1"]);
+        let err = run_one(&args, "-i64::MIN").err().unwrap();
+        assert_eq!(err.location().unwrap().span, 1..9);
+        assert!(run_one(&args, "-12_345").is_ok());
     }
 
     #[test]
-    fn dogfood_unsigned() {
-        for i in 0..1025 {
-            let mut buf = [0u8; 1024];
+    fn test_decode_offsets() {
+        // Decoded values, errors and explanations all count offsets from 0.
+        let input = "e58e26 7f 80";
+        let args = parse_args(&["du", "--all", input]);
+        let (values, err) = decoded(run_one(&args, input));
+        assert_eq!(
+            values,
+            vec![(0, 3, "624485".to_owned()), (3, 1, "127".to_owned())]
+        );
+        assert_eq!(
+            err.unwrap(),
+            "byte at offset 4 sets continuation bit but input ends here\n    \
+             e58e26 7f 80\n              ^^"
+        );
 
-            {
-                let mut writable = &mut buf[..];
-                write::unsigned(&mut writable, i).expect("Should write signed number");
-            }
+        let explanation = explain::decode(&[0xe5, 0x8e, 0x26, 0x7f, 0x80], 0, false, 128, None);
+        assert!(
+            explanation.contains("value at offset 3:"),
+            "{}",
+            explanation
+        );
+        assert!(
+            explanation.contains("Continuation bit of the byte at offset 4 is set"),
+            "{}",
+            explanation
+        );
 
-            let mut readable = &buf[..];
-            let result =
-                read::unsigned(&mut readable).expect("Should be able to read it back again");
-            assert_eq!(i, result);
-        }
+        let args = parse_args(&["du", "--strict", "--all", "00 81 80 00"]);
+        assert_eq!(
+            decoded(run_one(&args, "00 81 80 00")),
+            (
+                vec![(0, 1, "0".to_owned())],
+                Some(
+                    "bytes at offsets 2-3 are redundant, the canonical encoding is 01\n    \
+                     00 81 80 00\n          ^^^^^"
+                        .to_owned()
+                )
+            )
+        );
     }
 
-    #[test]
-    fn test_read_unsigned_overflow() {
-        let buf = [
-            2u8 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            1,
-        ];
-        let mut readable = &buf[..];
-        assert!(read::unsigned(&mut readable).is_err());
-    }
+    const BINARY: &[u8] = &[0x00, 0xe5, 0x8e, 0x26, 0x7f, 0x80, 0x01, 0x80];
 
     #[test]
-    fn test_read_signed_overflow() {
-        let buf = [
-            2u8 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            2 | CONTINUATION_BIT,
-            1,
-        ];
-        let mut readable = &buf[..];
-        assert!(read::signed(&mut readable).is_err());
+    fn test_decode_binary() {
+        assert_eq!(
+            decoded(decode_bytes(&["du", "--offset", "1"], BINARY)),
+            (vec![(1, 3, "624485".to_owned())], None)
+        );
+        assert_eq!(
+            decoded(decode_bytes(&["ds", "--offset", "4"], BINARY)),
+            (vec![(4, 1, "-1".to_owned())], None)
+        );
+        assert_eq!(
+            decoded(decode_bytes(
+                &["du", "--offset", "1", "--count", "2"],
+                BINARY
+            )),
+            (
+                vec![(1, 3, "624485".to_owned()), (4, 1, "127".to_owned())],
+                None
+            )
+        );
+        assert_eq!(
+            decoded(decode_bytes(&["du", "--offset", "1", "--all"], BINARY)),
+            (
+                vec![
+                    (1, 3, "624485".to_owned()),
+                    (4, 1, "127".to_owned()),
+                    (5, 2, "128".to_owned())
+                ],
+                Some(
+                    "Value at offset 7 sets continuation bit on its last byte but the file ends \
+                     there"
+                        .to_owned()
+                )
+            )
+        );
+        assert_eq!(
+            decoded(decode_bytes(
+                &["du", "--offset", "1", "--length", "4", "--all"],
+                BINARY
+            )),
+            (
+                vec![(1, 3, "624485".to_owned()), (4, 1, "127".to_owned())],
+                None
+            )
+        );
+        assert_eq!(
+            decoded(decode_bytes(&["du", "--offset", "8", "--all"], BINARY)),
+            (vec![], None)
+        );
     }
 
     #[test]
-    fn test_read_multiple() {
-        let buf = [2u8 | CONTINUATION_BIT, 1u8, 1u8];
-
-        let mut readable = &buf[..];
+    fn test_decode_binary_errors() {
+        assert_eq!(
+            message_and_span(decode_bytes(
+                &["du", "--offset", "5", "--length", "1"],
+                BINARY
+            ))
+            .0,
+            "Value at offset 5 sets continuation bit on its last byte but the range given with \
+             --length ends there"
+        );
+        assert_eq!(
+            message_and_span(decode_bytes(&["du", "--offset", "7"], BINARY)).0,
+            "Value at offset 7 sets continuation bit on its last byte but the file ends there"
+        );
         assert_eq!(
-            read::unsigned(&mut readable).expect("Should read first number"),
-            130u128
+            message_and_span(decode_bytes(&["du", "--offset", "8"], BINARY)).0,
+            "No bytes to decode at offset 8"
         );
         assert_eq!(
-            read::unsigned(&mut readable).expect("Should read first number"),
-            1u128
+            message_and_span(decode_bytes(&["du", "--offset", "100"], BINARY)).0,
+            "No bytes to decode at offset 100"
+        );
+        assert_eq!(
+            message_and_span(decode_bytes(
+                &["du", "--bits", "8", "--offset", "1"],
+                BINARY
+            ))
+            .0,
+            "Value at offset 1: byte at offset 2 sets continuation bit but 8-bit values are at \
+             most 2 bytes long"
+        );
+        assert_eq!(
+            message_and_span(decode_bytes(&["du", "--strict"], &[0x81, 0x80, 0x00])).0,
+            "Value at offset 0 has 2 redundant bytes, the canonical encoding is 01"
+        );
+        assert_eq!(
+            message_and_span(decode_bytes(
+                &["du", "--strict", "--offset", "1"],
+                &[0x00, 0x81, 0x80, 0x00]
+            ))
+            .0,
+            "Value at offset 1 has 2 redundant bytes, the canonical encoding is 01"
         );
     }
 
     #[test]
-    fn test_read_multiple_with_overflow() {
-        let buf = [
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b1111_1111,
-            0b0111_1111, // Overflow!
-            0b1110_0100,
-            0b1110_0000,
-            0b0000_0010, // 45156
-        ];
-        let mut readable = &buf[..];
-
-        assert!(if let read::Error::Overflow =
-            read::unsigned(&mut readable).expect_err("Should fail with Error::Overflow")
-        {
-            true
-        } else {
-            false
-        });
-        assert_eq!(
-            read::unsigned(&mut readable).expect("Should succeed with correct value"),
-            45156
+    fn test_json_unread() {
+        let args = parse_args(&["du", "--json", "e58e26 7f"]);
+        let (json, _) = json_output(&args, "e58e26 7f", run_one(&args, "e58e26 7f"));
+        assert!(json.to_string().contains(r#""unread":"7f""#), "{}", json);
+
+        // The file isn't read past the decoded value.
+        let args = parse_args(&["du", "--json", "--binary", "test.bin"]);
+        let result = decode_bytes(&["du", "--json", "--offset", "1"], BINARY);
+        let (json, _) = json_output(&args, "test.bin", result);
+        assert!(
+            json.to_string().ends_with(
+                r#""bytes":"e58e26","canonical":"e58e26","length":3,"unread":"","error":null}"#
+            ),
+            "{}",
+            json
         );
     }
+
+    #[test]
+    fn test_decode_binary_both() {
+        match decode_bytes(&["db", "--offset", "4"], BINARY) {
+            Ok(Output::Both {
+                base,
+                offset,
+                len,
+                unsigned,
+                signed,
+                ..
+            }) => {
+                assert_eq!((base, offset, len), (4, 4, 1));
+                assert_eq!(unsigned.unwrap(), 127);
+                assert_eq!(signed.unwrap(), -1);
+            }
+            _ => panic!("Unexpected output"),
+        }
+    }
 }
@@ -0,0 +1,244 @@
+//! Formatting of encoded bytes.
+
+use std::fmt::{self, Write};
+use std::str::FromStr;
+
+/// How to print encoded bytes, selected with `--format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Format {
+    /// `e58e26`
+    #[default]
+    Hex,
+
+    /// `e5 8e 26`
+    Spaced,
+
+    /// `0xe5, 0x8e, 0x26`
+    Prefixed,
+
+    /// `{0xe5, 0x8e, 0x26}`
+    C,
+
+    /// `[0xe5, 0x8e, 0x26]`
+    Rust,
+
+    /// `b"\xe5\x8e\x26"`
+    ByteString,
+
+    /// `\xe5\x8e\x26`
+    Escape,
+
+    /// `5Y4m`
+    Base64,
+
+    /// `11100101 10001110 00100110`
+    Binary,
+
+    /// The bytes themselves.
+    Raw,
+}
+
+/// Names of the formats accepted by `--format`, with an example each.
+pub const FORMAT_NAMES: &[(&str, Format, &str)] = &[
+    ("hex", Format::Hex, "e58e26"),
+    ("spaced", Format::Spaced, "e5 8e 26"),
+    ("0x", Format::Prefixed, "0xe5, 0x8e, 0x26"),
+    ("c", Format::C, "{0xe5, 0x8e, 0x26}"),
+    ("rust", Format::Rust, "[0xe5, 0x8e, 0x26]"),
+    ("bytestr", Format::ByteString, "b\"\\xe5\\x8e\\x26\""),
+    ("escape", Format::Escape, "\\xe5\\x8e\\x26"),
+    ("base64", Format::Base64, "5Y4m"),
+    ("binary", Format::Binary, "11100101 10001110 00100110"),
+    ("raw", Format::Raw, "the bytes themselves"),
+];
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match FORMAT_NAMES.iter().find(|(name, _, _)| *name == s) {
+            Some((_, format, _)) => Ok(*format),
+            None => {
+                let names: Vec<&str> = FORMAT_NAMES.iter().map(|(name, _, _)| *name).collect();
+                Err(format!(
+                    "Unknown format {:?}, expected one of: {}",
+                    s,
+                    names.join(", ")
+                ))
+            }
+        }
+    }
+}
+
+/// Formats `bytes` as text. `upper` selects upper case hex digits. `Format::Raw` is not text and
+/// must be handled by the caller.
+pub fn format_bytes(bytes: &[u8], format: Format, upper: bool) -> String {
+    let hex = |byte: &u8| {
+        if upper {
+            format!("{:02X}", byte)
+        } else {
+            format!("{:02x}", byte)
+        }
+    };
+    let join = |prefix: &str, sep: &str| {
+        bytes
+            .iter()
+            .map(|byte| format!("{}{}", prefix, hex(byte)))
+            .collect::<Vec<_>>()
+            .join(sep)
+    };
+
+    match format {
+        Format::Hex => join("", ""),
+        Format::Spaced => join("", " "),
+        Format::Prefixed => join("0x", ", "),
+        Format::C => format!("{{{}}}", join("0x", ", ")),
+        Format::Rust => format!("[{}]", join("0x", ", ")),
+        Format::ByteString => format!("b\"{}\"", join("\\x", "")),
+        Format::Escape => join("\\x", ""),
+        Format::Base64 => base64(bytes),
+        Format::Binary => bytes
+            .iter()
+            .map(|byte| format!("{:08b}", byte))
+            .collect::<Vec<_>>()
+            .join(" "),
+        Format::Raw => panic!("Raw bytes can't be formatted as text"),
+    }
+}
+
+/// Standard base64 (RFC 4648) with padding.
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+    let mut s = String::new();
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, byte)| n | (*byte as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                let sextet = (n >> (18 - 6 * i)) & 0x3f;
+                s.push(ALPHABET[sextet as usize] as char);
+            } else {
+                s.push('=');
+            }
+        }
+    }
+    s
+}
+
+/// A JSON value, for `--json` output.
+#[derive(Debug)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(i128),
+    String(String),
+    Array(Vec<Json>),
+    /// Keys are printed in the given order, so the output is stable.
+    Object(Vec<(&'static str, Json)>),
+}
+
+impl Json {
+    pub fn string<S: ToString>(s: S) -> Json {
+        Json::String(s.to_string())
+    }
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> Result<(), fmt::Error> {
+        match self {
+            Json::Null => write!(f, "null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) => write!(f, "{}", n),
+            Json::String(s) => {
+                f.write_char('"')?;
+                for c in s.chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        '\r' => f.write_str("\\r")?,
+                        '\t' => f.write_str("\\t")?,
+                        c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+                        c => f.write_char(c)?,
+                    }
+                }
+                f.write_char('"')
+            }
+            Json::Array(values) => {
+                f.write_char('[')?;
+                for (i, value) in values.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(',')?;
+                    }
+                    value.fmt(f)?;
+                }
+                f.write_char(']')
+            }
+            Json::Object(fields) => {
+                f.write_char('{')?;
+                for (i, (key, value)) in fields.iter().enumerate() {
+                    if i != 0 {
+                        f.write_char(',')?;
+                    }
+                    write!(f, "\"{}\":{}", key, value)?;
+                }
+                f.write_char('}')
+            }
+        }
+    }
+}
+
+/// The list of formats shown in `--help`.
+pub fn formats_help() -> String {
+    let mut s = String::new();
+    for (name, _, example) in FORMAT_NAMES {
+        let _ = writeln!(s, "    {:<22} {}", name, example);
+    }
+    s
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_bytes() {
+        let bytes = [0xe5, 0x8e, 0x26];
+        for (name, format, example) in FORMAT_NAMES {
+            if *format != Format::Raw {
+                assert_eq!(&format_bytes(&bytes, *format, false), example, "{}", name);
+            }
+        }
+        assert_eq!(
+            format_bytes(&bytes, Format::Prefixed, true),
+            "0xE5, 0x8E, 0x26"
+        );
+    }
+
+    #[test]
+    fn test_json() {
+        let json = Json::Object(vec![
+            ("null", Json::Null),
+            ("bool", Json::Bool(true)),
+            ("number", Json::Number(-1)),
+            ("string", Json::string("a\"b\\c\nd\u{1}")),
+            ("array", Json::Array(vec![Json::Number(1), Json::Number(2)])),
+        ]);
+        assert_eq!(
+            json.to_string(),
+            r#"{"null":null,"bool":true,"number":-1,"string":"a\"b\\c\nd\u0001","array":[1,2]}"#
+        );
+    }
+
+    #[test]
+    fn test_base64() {
+        assert_eq!(base64(b""), "");
+        assert_eq!(base64(b"f"), "Zg==");
+        assert_eq!(base64(b"fo"), "Zm8=");
+        assert_eq!(base64(b"foo"), "Zm9v");
+        assert_eq!(base64(b"foob"), "Zm9vYg==");
+    }
+}
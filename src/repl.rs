@@ -0,0 +1,207 @@
+//! Interactive mode, started when `leb128` is run without arguments.
+
+use super::*;
+
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+
+use std::path::PathBuf;
+
+const PROMPT: &str = "leb128> ";
+
+/// History is persisted in `$HOME/.leb128_history`. When `$HOME` is not set history is kept only
+/// for the current session.
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".leb128_history"))
+}
+
+/// Runs the REPL until `quit` or end of input. Fails only when the line editor can't be set up,
+/// e.g. when the terminal can't be configured.
+pub fn run() -> Result<(), Error> {
+    let mut editor = DefaultEditor::new().map_err(|err| {
+        Error::Io(io::Error::other(format!(
+            "unable to initialize line editor: {}",
+            err
+        )))
+    })?;
+
+    let history_path = history_path();
+    if let Some(path) = &history_path {
+        // The file won't exist on first run, ignore errors.
+        let _ = editor.load_history(path);
+    }
+
+    show_repl_help();
+
+    loop {
+        let line = match editor.readline(PROMPT) {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(err) => {
+                eprintln!("Error while reading input: {}", err);
+                break;
+            }
+        };
+
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let _ = editor.add_history_entry(line);
+
+        match line {
+            "quit" | "exit" | "q" => break,
+            "?" => {
+                show_repl_help();
+                continue;
+            }
+            _ => {}
+        }
+
+        let words = match split_words(line) {
+            Some(words) => words,
+            None => {
+                eprintln!("error: unterminated quote");
+                continue;
+            }
+        };
+
+        match cli::parse(words) {
+            Ok(Invocation::Empty) => {}
+            Ok(Invocation::Help(None)) => show_repl_help(),
+            Ok(Invocation::Help(Some(cmd))) => print!("{}", cli::cmd_usage(cmd)),
+            Ok(Invocation::Version) => println!("{}", cli::version()),
+            // The terminal belongs to the line editor, inputs can't be read from stdin.
+            Ok(Invocation::Run(args)) if args.source == Source::Stdin => {
+                eprintln!("error: {}", cli::missing_argument(args.cmd));
+            }
+            Ok(Invocation::Run(args)) => {
+                run_cmd(&args);
+            }
+            Err(err) => {
+                eprintln!("error: {}", err);
+                match err.cmd {
+                    Some(cmd) => eprintln!("Run `{} --help` for usage.", cmd.short_name()),
+                    None => eprintln!("Run `help` for a list of commands."),
+                }
+            }
+        }
+    }
+
+    if let Some(path) = &history_path {
+        if let Err(err) = editor.save_history(path) {
+            eprintln!("Unable to save history to {}: {}", path.display(), err);
+        }
+    }
+
+    Ok(())
+}
+
+fn show_repl_help() {
+    println!("Commands:");
+    println!("  es <expr>...  Encode signed numbers, e.g. `es -(2**31)` or `es \"1 << 35\"`");
+    println!("  eu <expr>...  Encode unsigned numbers, e.g. `eu u32::MAX 300`");
+    println!("  ds <bytes>    Decode a signed number from hex bytes");
+    println!("  du <bytes>    Decode an unsigned number from hex bytes");
+    println!("  db <bytes>    Decode hex bytes as both unsigned and signed");
+    println!("  help [cmd]    Show this message, or the options of a command");
+    println!("  quit          Exit (Ctrl-D also works)");
+}
+
+/// Splits a line into words like a shell would: on whitespace, except inside single or double
+/// quotes. There are no escapes: backslashes are kept, so hex bytes like `\xe5` can be typed as
+/// they are. Returns `None` if a quote is not terminated.
+fn split_words(line: &str) -> Option<Vec<String>> {
+    let mut words = vec![];
+    let mut word = String::new();
+    let mut in_word = false;
+    let mut quote: Option<char> = None;
+
+    for c in line.chars() {
+        match quote {
+            Some(q) if c == q => quote = None,
+            Some(_) => word.push(c),
+            None if c == '"' || c == '\'' => {
+                quote = Some(c);
+                in_word = true;
+            }
+            None if c.is_whitespace() => {
+                if in_word {
+                    words.push(std::mem::take(&mut word));
+                    in_word = false;
+                }
+            }
+            None => {
+                word.push(c);
+                in_word = true;
+            }
+        }
+    }
+
+    if quote.is_some() {
+        return None;
+    }
+
+    if in_word {
+        words.push(word);
+    }
+
+    Some(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(words: &[&str]) -> Option<Vec<String>> {
+        Some(words.iter().map(|word| word.to_string()).collect())
+    }
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(split_words("eu 1 2"), words(&["eu", "1", "2"]));
+        assert_eq!(split_words("  eu\t1   2  "), words(&["eu", "1", "2"]));
+        assert_eq!(split_words(""), words(&[]));
+        assert_eq!(split_words("   "), words(&[]));
+    }
+
+    #[test]
+    fn test_split_words_quotes() {
+        assert_eq!(
+            split_words("du 'e5 8e' \"26\""),
+            words(&["du", "e5 8e", "26"])
+        );
+        assert_eq!(
+            split_words("eu '1 << 35' \"u32::MAX + 1\""),
+            words(&["eu", "1 << 35", "u32::MAX + 1"])
+        );
+        // Quotes can start or end in the middle of a word, and quote the other kind of quote.
+        assert_eq!(split_words("a'b c'd"), words(&["ab cd"]));
+        assert_eq!(split_words("'\"' \"'\""), words(&["\"", "'"]));
+        // Empty quotes are an empty word.
+        assert_eq!(split_words("du ''"), words(&["du", ""]));
+        assert_eq!(split_words("du \"\" 00"), words(&["du", "", "00"]));
+    }
+
+    #[test]
+    fn test_split_words_backslashes() {
+        assert_eq!(
+            split_words("du \\xe5\\x8e\\x26"),
+            words(&["du", "\\xe5\\x8e\\x26"])
+        );
+        assert_eq!(split_words("a\\ b"), words(&["a\\", "b"]));
+        assert_eq!(split_words("'a\\' b"), words(&["a\\", "b"]));
+        assert_eq!(split_words("\\'a b'"), words(&["\\a b"]));
+    }
+
+    #[test]
+    fn test_split_words_unterminated() {
+        assert_eq!(split_words("du 'e5 8e"), None);
+        assert_eq!(split_words("eu '1 << 35"), None);
+        assert_eq!(split_words("du \"e5"), None);
+        assert_eq!(split_words("du 'e5\""), None);
+        assert_eq!(split_words("'"), None);
+    }
+}